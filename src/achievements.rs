@@ -0,0 +1,257 @@
+//! Badges and scoreboards of an app: what the ROM declares and what the player got.
+
+use firefly_runtime::FullID;
+use firefly_types::{Badges, Boards, Encode as _};
+use kaolin::prelude::*;
+use macroquad::prelude::*;
+
+use crate::{
+    dir,
+    i18n::Text,
+    nav::{self, Nav},
+    safe_area,
+    settings::Settings,
+    stats,
+    ui::{self, BUTTON_FONT_SIZE, DESCR_FONT_SIZE, TITLE_FONT_SIZE},
+};
+
+/// How many of the best own scores to show per board.
+const TOP_SCORES: usize = 3;
+
+struct Row {
+    label: String,
+    value: String,
+    /// Earned badges are highlighted
+    earned: bool,
+}
+
+/// A badge declared by the ROM with the progress from the stats.
+struct Badge {
+    name: String,
+    descr: String,
+    xp: u8,
+    /// Don't reveal the badge until this much progress is made
+    hidden: u16,
+    done: u16,
+    goal: u16,
+}
+
+/// A scoreboard declared by the ROM with the best own scores from the stats.
+struct Board {
+    name: String,
+    time: bool,
+    decimals: u8,
+    scores: Vec<i16>,
+}
+
+fn load_badges(id: &FullID, progress: &[firefly_types::BadgeProgress]) -> Vec<Badge> {
+    let path = dir()
+        .join("roms")
+        .join(id.author())
+        .join(id.app())
+        .join("_badges");
+    let Ok(raw) = std::fs::read(path) else {
+        return vec![];
+    };
+    let Ok(badges) = Badges::decode(&raw) else {
+        return vec![];
+    };
+    badges
+        .badges
+        .iter()
+        .enumerate()
+        .map(|(i, badge)| {
+            let (done, goal) = progress.get(i).map_or((0, 1), |p| (p.done, p.goal));
+            Badge {
+                name: badge.name.to_owned(),
+                descr: badge.descr.to_owned(),
+                xp: badge.xp,
+                hidden: badge.hidden.into(),
+                done,
+                goal,
+            }
+        })
+        .collect()
+}
+
+fn load_boards(id: &FullID, scores: &[firefly_types::BoardScores]) -> Vec<Board> {
+    let path = dir()
+        .join("roms")
+        .join(id.author())
+        .join(id.app())
+        .join("_boards");
+    let Ok(raw) = std::fs::read(path) else {
+        return vec![];
+    };
+    let Ok(boards) = Boards::decode(&raw) else {
+        return vec![];
+    };
+    boards
+        .boards
+        .iter()
+        .enumerate()
+        .map(|(i, board)| Board {
+            name: board.name.to_owned(),
+            time: board.time,
+            decimals: board.decimals,
+            // The scores are kept sorted, zero means no score.
+            scores: scores.get(i).map_or(vec![], |s| {
+                s.me.iter()
+                    .copied()
+                    .filter(|score| *score != 0)
+                    .take(TOP_SCORES)
+                    .collect()
+            }),
+        })
+        .collect()
+}
+
+/// Show the score the way the board declares it: as time or with a decimal point.
+fn format_score(board: &Board, score: i16) -> String {
+    if board.time {
+        let sign = if score < 0 { "-" } else { "" };
+        let secs = i32::from(score).abs();
+        return format!("{sign}{}:{:02}", secs / 60, secs % 60);
+    }
+    if board.decimals == 0 {
+        return score.to_string();
+    }
+    let div = 10_f64.powi(board.decimals.into());
+    format!("{:.*}", usize::from(board.decimals), f64::from(score) / div)
+}
+
+pub struct AchievementsScreen {
+    settings: Settings,
+    rows: Vec<Row>,
+    ui: ui::Renderer,
+}
+
+impl AchievementsScreen {
+    pub fn new(id: &FullID, settings: &Settings) -> Self {
+        let mut rows = vec![];
+        let row = |label: &str, value: String, earned: bool| Row {
+            label: label.to_owned(),
+            value,
+            earned,
+        };
+        if let Some(stats) = stats::load(id) {
+            let tr = move |text| settings.tr(text);
+            rows.push(row(tr(Text::Xp), stats.xp.to_string(), false));
+            for badge in load_badges(id, &stats.badges) {
+                let earned = badge.done >= badge.goal;
+                if badge.done < badge.hidden {
+                    rows.push(row("???", tr(Text::Locked).to_owned(), false));
+                    continue;
+                }
+                let value = if earned {
+                    format!("{} ({} XP)", tr(Text::Earned), badge.xp)
+                } else {
+                    format!("{}/{} ({} XP)", badge.done, badge.goal, badge.xp)
+                };
+                rows.push(row(
+                    &format!("{}: {}", badge.name, badge.descr),
+                    value,
+                    earned,
+                ));
+            }
+            for board in load_boards(id, &stats.scores) {
+                let value = if board.scores.is_empty() {
+                    tr(Text::NoScores).to_owned()
+                } else {
+                    let scores: Vec<_> = board
+                        .scores
+                        .iter()
+                        .map(|score| format_score(&board, *score))
+                        .collect();
+                    scores.join(", ")
+                };
+                rows.push(row(&board.name, value, false));
+            }
+        }
+        AchievementsScreen {
+            settings: settings.clone(),
+            rows,
+            ui: ui::Renderer::new(safe_area::insets(settings)),
+        }
+    }
+
+    pub async fn show(&mut self) -> Nav {
+        loop {
+            if nav::back_pressed() {
+                return Nav::Pop;
+            }
+            let theme = self.settings.theme;
+            self.ui.insets = safe_area::insets(&self.settings);
+            clear_background(theme.background());
+            let rows = &self.rows;
+            let title = self.settings.tr(Text::Achievements);
+            let empty = self.settings.tr(Text::NoAchievements);
+            let label_width = safe_area::rect(&self.settings).w as f64 / 2.;
+            self.ui.draw(|k| {
+                let style = TextStyle::new()
+                    .font_size(DESCR_FONT_SIZE)
+                    .color(theme.foreground().into());
+                let border = || Border {
+                    width: 3.,
+                    color: theme.border().into(),
+                };
+                k.styled(
+                    FlexStyle::new()
+                        .background_color(theme.background().into())
+                        .layout(Layout::new().direction(Direction::TopToBottom))
+                        .sizing(sizing!(grow!())),
+                    |mut k| {
+                        k = k.styled(
+                            FlexStyle::new()
+                                .background_color(theme.header().into())
+                                .layout(
+                                    Layout::new()
+                                        .alignment(Alignment::Center)
+                                        .justification(Justification::Center),
+                                )
+                                .sizing(sizing!(grow!(), fit!())),
+                            |k| k.text(title, style.font_size(TITLE_FONT_SIZE)),
+                        );
+                        if rows.is_empty() {
+                            k = k.text(empty, style);
+                        }
+                        for row in rows {
+                            let color = if row.earned {
+                                GREEN
+                            } else {
+                                theme.foreground()
+                            };
+                            k = k.styled(
+                                FlexStyle::new()
+                                    .border(border())
+                                    .layout(Layout::new().direction(Direction::LeftToRight))
+                                    .sizing(sizing!(grow!(), fit!())),
+                                |k| {
+                                    k.styled(
+                                        FlexStyle::new()
+                                            .border(border())
+                                            .sizing(sizing!(fixed!(label_width), grow!())),
+                                        |k| k.text(&row.label, style.color(color.into())),
+                                    )
+                                    .styled(
+                                        FlexStyle::new().border(border()).sizing(sizing!(grow!())),
+                                        |k| {
+                                            k.text(
+                                                &row.value,
+                                                style
+                                                    .font_size(BUTTON_FONT_SIZE / 2.)
+                                                    .color(color.into()),
+                                            )
+                                        },
+                                    )
+                                },
+                            );
+                        }
+                        k
+                    },
+                )
+            });
+            next_frame().await;
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Sound output for the running app.
+//!
+//! The runtime mixes the audio of the app on every update. The samples are queued
+//! in a ring buffer that the audio thread of the platform drains at its own pace,
+//! converting them to whatever sample rate, format and channel count the device uses.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    FromSample, SampleFormat, SizedSample,
+};
+
+use crate::runtime_log;
+
+/// Sample rate of the Firefly Zero audio mixer.
+pub const SAMPLE_RATE: u32 = 44_100;
+/// The mixer produces interleaved stereo samples.
+const CHANNELS: u16 = 2;
+
+struct Queue {
+    samples: VecDeque<i16>,
+    /// How far playback is between the first two queued frames, from 0 to 1
+    pos: f32,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Master volume in percent
+    volume: AtomicU8,
+    muted: AtomicBool,
+}
+
+pub struct AudioOutput {
+    shared: Arc<Shared>,
+    /// How many samples may be queued before the oldest ones are dropped
+    max_queued: usize,
+    // Audio stops when the stream is dropped.
+    _stream: cpal::Stream,
+}
+
+impl AudioOutput {
+    /// Open the default output device with its preferred configuration.
+    ///
+    /// Returns `None` if there is no usable audio device, in which case apps run silently.
+    pub fn new(volume: u8, latency_ms: u16) -> Option<Self> {
+        let device = cpal::default_host().default_output_device()?;
+        let supported = device
+            .default_output_config()
+            .inspect_err(|e| runtime_log::record(format!("failed to query audio output: {e}")))
+            .ok()?;
+        let max_queued =
+            SAMPLE_RATE as usize * usize::from(CHANNELS) * usize::from(latency_ms) / 1000;
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                samples: VecDeque::with_capacity(max_queued),
+                pos: 0.,
+            }),
+            volume: AtomicU8::new(volume),
+            muted: AtomicBool::new(false),
+        });
+        let config = supported.config();
+        let reader = shared.clone();
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build::<f32>(&device, &config, reader),
+            SampleFormat::I16 => build::<i16>(&device, &config, reader),
+            SampleFormat::U16 => build::<u16>(&device, &config, reader),
+            SampleFormat::I32 => build::<i32>(&device, &config, reader),
+            format => {
+                runtime_log::record(format!("unsupported audio sample format {format:?}"));
+                return None;
+            }
+        }
+        .inspect_err(|e| runtime_log::record(format!("failed to open audio output: {e}")))
+        .ok()?;
+        stream.play().ok()?;
+        Some(AudioOutput {
+            shared,
+            max_queued,
+            _stream: stream,
+        })
+    }
+
+    /// Queue samples produced by the mixer.
+    ///
+    /// If the queue grows beyond the configured latency, for example because the app
+    /// ran faster than real time, the oldest samples are dropped to catch up.
+    pub fn push(&self, samples: &[i16]) {
+        let queue = &mut self.shared.queue.lock().unwrap().samples;
+        queue.extend(samples);
+        let excess = queue.len().saturating_sub(self.max_queued);
+        // Drop whole frames so that the channels don't get swapped.
+        let excess = excess
+            .next_multiple_of(usize::from(CHANNELS))
+            .min(queue.len());
+        queue.drain(..excess);
+    }
+
+    pub fn set_volume(&self, volume: u8) {
+        self.shared.volume.store(volume, Ordering::Relaxed);
+    }
+
+    /// Silence the output and discard anything queued, for example while paused.
+    pub fn set_muted(&self, muted: bool) {
+        self.shared.muted.store(muted, Ordering::Relaxed);
+        if muted {
+            self.shared.queue.lock().unwrap().samples.clear();
+        }
+    }
+}
+
+fn build<T: SizedSample + FromSample<f32>>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    shared: Arc<Shared>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let channels = usize::from(config.channels);
+    // How many mixer frames to advance per output frame.
+    let step = SAMPLE_RATE as f32 / config.sample_rate.0 as f32;
+    device.build_output_stream(
+        config,
+        move |out: &mut [T], _| shared.fill(out, channels, step),
+        |e| runtime_log::record(format!("audio stream error: {e}")),
+        None,
+    )
+}
+
+impl Shared {
+    /// Resample the queued stereo frames by linear interpolation into `channels` channels.
+    fn fill<T: SizedSample + FromSample<f32>>(&self, out: &mut [T], channels: usize, step: f32) {
+        let volume = if self.muted.load(Ordering::Relaxed) {
+            0.
+        } else {
+            f32::from(self.volume.load(Ordering::Relaxed)) / 100.
+        };
+        let mut queue = self.queue.lock().unwrap();
+        for frame in out.chunks_mut(channels) {
+            let (left, right) = queue.next(step);
+            let (left, right) = (left * volume, right * volume);
+            match frame {
+                [mono] => *mono = T::from_sample((left + right) / 2.),
+                [l, r, rest @ ..] => {
+                    *l = T::from_sample(left);
+                    *r = T::from_sample(right);
+                    rest.fill(T::EQUILIBRIUM);
+                }
+                [] => {}
+            }
+        }
+    }
+}
+
+impl Queue {
+    /// The next output frame as left and right from -1 to 1.
+    fn next(&mut self, step: f32) -> (f32, f32) {
+        // Play silence when running out of samples, e.g. when the app is in the background.
+        let s = &self.samples;
+        if s.len() < 4 {
+            return (0., 0.);
+        }
+        let [l0, r0, l1, r1] = [s[0], s[1], s[2], s[3]].map(|s| f32::from(s) / 32768.);
+        let pos = self.pos.min(1.);
+        self.pos += step;
+        while self.pos >= 1. && self.samples.len() >= 4 {
+            self.samples.drain(..2);
+            self.pos -= 1.;
+        }
+        (l0 + (l1 - l0) * pos, r0 + (r1 - r0) * pos)
+    }
+}
@@ -0,0 +1,644 @@
+//! Exporting the data and stats of apps into a zip archive and importing them back.
+//!
+//! The archive holds the data directory of every exported app as `<author>/<app>/...`.
+//!
+//! On desktop, archives go outside of the emulator's own directory, so that they survive
+//! uninstalling it or clearing its data. Android keeps them with the emulator's data,
+//! copies them into the shared downloads, and offers to send them to another app.
+
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use firefly_runtime::FullID;
+use kaolin::prelude::*;
+use macroquad::prelude::*;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    copy_dir, dir,
+    i18n::Text,
+    nav::{self, Nav},
+    safe_area,
+    settings::Settings,
+    ui::{self, BUTTON_FONT_SIZE, DESCR_FONT_SIZE, TITLE_FONT_SIZE},
+};
+
+fn data_root() -> PathBuf {
+    dir().join("data")
+}
+
+/// Where archives are written to and imported from: the folder from the settings or,
+/// by default, one in the shared documents if there is one.
+pub fn backups_dir(settings: &Settings) -> PathBuf {
+    match &settings.backup_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => match shared_dir() {
+            Some(shared) => shared.join("fireflydroid"),
+            // Better than nothing, but lost with the emulator's own data.
+            None => dir().join("backups"),
+        },
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+fn shared_dir() -> Option<PathBuf> {
+    let dirs = directories::UserDirs::new()?;
+    let documents = dirs.document_dir().unwrap_or(dirs.home_dir());
+    Some(documents.to_owned())
+}
+
+/// Android has no shared folder that apps can list without a permission.
+/// Archives are kept with the emulator's own data and [published](publish) instead.
+#[cfg(target_os = "android")]
+fn shared_dir() -> Option<PathBuf> {
+    None
+}
+
+/// Where published archives show up, relative to the shared storage.
+#[cfg(target_os = "android")]
+const DOWNLOADS: &str = "Download/fireflydroid";
+
+/// Copy the archive into the shared downloads, where it survives uninstalling
+/// the emulator, and offer to send it to another app or device.
+///
+/// Returns where the archive ended up, for showing it to the user.
+#[cfg(target_os = "android")]
+fn publish(path: &Path) -> io::Result<String> {
+    use std::os::fd::FromRawFd;
+
+    use macroquad::miniquad::{
+        call_int_method, call_object_method, call_void_method,
+        native::android::{attach_jni_env, ACTIVITY},
+        new_object,
+    };
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    unsafe {
+        let env = attach_jni_env();
+        let string = |s: &str| {
+            let s = std::ffi::CString::new(s).unwrap();
+            ((**env).NewStringUTF.unwrap())(env, s.as_ptr())
+        };
+        let class =
+            ((**env).FindClass.unwrap())(env, c"android/provider/MediaStore$Downloads".as_ptr());
+        if class.is_null() {
+            // Added in Android 10, older versions only keep the archive in the backups folder.
+            ((**env).ExceptionClear.unwrap())(env);
+            return Ok(path.display().to_string());
+        }
+        let field = ((**env).GetStaticFieldID.unwrap())(
+            env,
+            class,
+            c"EXTERNAL_CONTENT_URI".as_ptr(),
+            c"Landroid/net/Uri;".as_ptr(),
+        );
+        java_error(env)?;
+        let downloads = ((**env).GetStaticObjectField.unwrap())(env, class, field);
+
+        let values = new_object!(env, "android/content/ContentValues", "()V");
+        for (key, value) in [
+            ("_display_name", name.as_ref()),
+            ("mime_type", "application/zip"),
+            ("relative_path", DOWNLOADS),
+        ] {
+            call_void_method!(
+                env,
+                values,
+                "put",
+                "(Ljava/lang/String;Ljava/lang/String;)V",
+                string(key),
+                string(value)
+            );
+        }
+        let resolver = call_object_method!(
+            env,
+            ACTIVITY,
+            "getContentResolver",
+            "()Landroid/content/ContentResolver;"
+        );
+        let uri = call_object_method!(
+            env,
+            resolver,
+            "insert",
+            "(Landroid/net/Uri;Landroid/content/ContentValues;)Landroid/net/Uri;",
+            downloads,
+            values
+        );
+        java_error(env)?;
+        if uri.is_null() {
+            return Err(io::Error::other("the downloads refused the archive"));
+        }
+        let descriptor = call_object_method!(
+            env,
+            resolver,
+            "openFileDescriptor",
+            "(Landroid/net/Uri;Ljava/lang/String;)Landroid/os/ParcelFileDescriptor;",
+            uri,
+            string("w")
+        );
+        java_error(env)?;
+        let fd = call_int_method!(env, descriptor, "detachFd", "()I");
+        java_error(env)?;
+        io::copy(&mut File::open(path)?, &mut File::from_raw_fd(fd))?;
+
+        let intent = new_object!(
+            env,
+            "android/content/Intent",
+            "(Ljava/lang/String;)V",
+            string("android.intent.action.SEND")
+        );
+        call_object_method!(
+            env,
+            intent,
+            "setType",
+            "(Ljava/lang/String;)Landroid/content/Intent;",
+            string("application/zip")
+        );
+        call_object_method!(
+            env,
+            intent,
+            "putExtra",
+            "(Ljava/lang/String;Landroid/os/Parcelable;)Landroid/content/Intent;",
+            string("android.intent.extra.STREAM"),
+            uri
+        );
+        // FLAG_GRANT_READ_URI_PERMISSION, so that the receiving app can read it.
+        call_object_method!(env, intent, "addFlags", "(I)Landroid/content/Intent;", 1);
+        let class = ((**env).FindClass.unwrap())(env, c"android/content/Intent".as_ptr());
+        let create_chooser = ((**env).GetStaticMethodID.unwrap())(
+            env,
+            class,
+            c"createChooser".as_ptr(),
+            c"(Landroid/content/Intent;Ljava/lang/CharSequence;)Landroid/content/Intent;".as_ptr(),
+        );
+        let chooser = ((**env).CallStaticObjectMethod.unwrap())(
+            env,
+            class,
+            create_chooser,
+            intent,
+            std::ptr::null_mut::<std::ffi::c_void>(),
+        );
+        java_error(env)?;
+        call_void_method!(
+            env,
+            ACTIVITY,
+            "startActivity",
+            "(Landroid/content/Intent;)V",
+            chooser
+        );
+        java_error(env)?;
+    }
+    Ok(format!("{DOWNLOADS}/{name}"))
+}
+
+/// Turn the exception thrown by the last Java call into an error.
+///
+/// It has to be cleared before calling into Java again.
+#[cfg(target_os = "android")]
+unsafe fn java_error(
+    env: *mut macroquad::miniquad::native::android::ndk_sys::JNIEnv,
+) -> io::Result<()> {
+    if ((**env).ExceptionCheck.unwrap())(env) == 0 {
+        return Ok(());
+    }
+    // Prints the exception to logcat.
+    ((**env).ExceptionDescribe.unwrap())(env);
+    ((**env).ExceptionClear.unwrap())(env);
+    Err(io::Error::other("Android refused to publish the archive"))
+}
+
+/// Archives stay where they were written to on desktop.
+#[cfg(not(target_os = "android"))]
+fn publish(path: &Path) -> io::Result<String> {
+    Ok(path.display().to_string())
+}
+
+/// `author/app` of every app that has a data directory.
+fn apps_with_data() -> Vec<String> {
+    let mut apps = vec![];
+    let Ok(authors) = std::fs::read_dir(data_root()) else {
+        return apps;
+    };
+    for author in authors.flatten() {
+        let Ok(author_apps) = std::fs::read_dir(author.path()) else {
+            continue;
+        };
+        for app in author_apps.flatten() {
+            apps.push(format!(
+                "{}/{}",
+                author.file_name().to_string_lossy(),
+                app.file_name().to_string_lossy()
+            ));
+        }
+    }
+    apps.sort();
+    apps
+}
+
+/// Write the data of the given apps into a new archive in the backups directory.
+fn export(backups: &Path, apps: &[String]) -> io::Result<PathBuf> {
+    std::fs::create_dir_all(backups)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = backups.join(format!("{now}.zip"));
+    let mut zip = ZipWriter::new(File::create(&path)?);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Zstd);
+    for app in apps {
+        add_dir(&mut zip, options, &data_root().join(app), app)?;
+    }
+    zip.finish()?;
+    Ok(path)
+}
+
+fn add_dir(
+    zip: &mut ZipWriter<File>,
+    options: SimpleFileOptions,
+    path: &Path,
+    name: &str,
+) -> io::Result<()> {
+    zip.add_directory(name, options)?;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let name = format!("{name}/{}", entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            add_dir(zip, options, &entry.path(), &name)?;
+        } else {
+            zip.start_file(name, options)?;
+            zip.write_all(&std::fs::read(entry.path())?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Backup archives, newest first.
+fn archives(backups: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(backups) else {
+        return vec![];
+    };
+    let mut archives: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e == "zip"))
+        .collect();
+    archives.sort();
+    archives.reverse();
+    archives
+}
+
+/// The `author/app` part of the path of an archive entry.
+fn app_of(path: &Path) -> Option<String> {
+    let mut parts = path.components();
+    let author = parts.next()?.as_os_str().to_string_lossy();
+    let app = parts.next()?.as_os_str().to_string_lossy();
+    Some(format!("{author}/{app}"))
+}
+
+/// Apps in the archive, each with how to handle it by default.
+fn apps_in(path: &Path) -> io::Result<Vec<(String, Resolution)>> {
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+    let mut apps: Vec<String> = vec![];
+    for i in 0..zip.len() {
+        let entry = zip.by_index(i)?;
+        let Some(app) = entry.enclosed_name().as_deref().and_then(app_of) else {
+            continue;
+        };
+        if !apps.contains(&app) {
+            apps.push(app);
+        }
+    }
+    apps.sort();
+    let local = apps_with_data();
+    Ok(apps
+        .into_iter()
+        .map(|app| {
+            // Don't touch existing data unless asked to.
+            let resolution = if local.contains(&app) {
+                Resolution::Skip
+            } else {
+                Resolution::Overwrite
+            };
+            (app, resolution)
+        })
+        .collect())
+}
+
+/// What to do with an app from the archive.
+#[derive(Copy, Clone, PartialEq)]
+enum Resolution {
+    /// Replace the local data with the one from the archive
+    Overwrite,
+    /// Only add files that don't exist locally
+    Merge,
+    Skip,
+}
+
+impl Resolution {
+    fn next(self) -> Self {
+        match self {
+            Resolution::Overwrite => Resolution::Merge,
+            Resolution::Merge => Resolution::Skip,
+            Resolution::Skip => Resolution::Overwrite,
+        }
+    }
+
+    fn text(self) -> Text {
+        match self {
+            Resolution::Overwrite => Text::Overwrite,
+            Resolution::Merge => Text::Merge,
+            Resolution::Skip => Text::Skip,
+        }
+    }
+}
+
+/// Unpack the apps next to the local data first and only replace it once that worked,
+/// so that a broken archive or a full disk doesn't leave any app with half of its data.
+fn import(path: &Path, apps: &[(String, Resolution)]) -> io::Result<()> {
+    let staging = dir()
+        .join("tmp")
+        .join(format!("import-{}", std::process::id()));
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    let result = extract(path, apps, &staging.join("new")).and_then(|()| swap(apps, &staging));
+    _ = std::fs::remove_dir_all(&staging);
+    result
+}
+
+fn extract(path: &Path, apps: &[(String, Resolution)], to: &Path) -> io::Result<()> {
+    // Merging only adds to what's there, so start from a copy of it.
+    for (app, resolution) in apps {
+        if *resolution == Resolution::Merge {
+            copy_dir(&data_root().join(app), &to.join(app))?;
+        }
+    }
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        // Skips entries that would end up outside of the data directory.
+        let Some(name) = entry.enclosed_name() else {
+            continue;
+        };
+        let resolution = app_of(&name)
+            .and_then(|app| apps.iter().find(|(a, _)| *a == app))
+            .map_or(Resolution::Skip, |(_, r)| *r);
+        let target = to.join(&name);
+        if entry.is_dir() {
+            if resolution != Resolution::Skip {
+                std::fs::create_dir_all(&target)?;
+            }
+            continue;
+        }
+        match resolution {
+            Resolution::Skip => continue,
+            Resolution::Merge if target.exists() => continue,
+            _ => {}
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut File::create(target)?)?;
+    }
+    Ok(())
+}
+
+/// Move the unpacked apps from `staging/new` in place of the local data.
+///
+/// Renames don't fail halfway, so every app ends up with either its old or its new data.
+fn swap(apps: &[(String, Resolution)], staging: &Path) -> io::Result<()> {
+    for (app, _) in apps {
+        let new = staging.join("new").join(app);
+        if !new.exists() {
+            continue;
+        }
+        let data = data_root().join(app);
+        let old = staging.join("old").join(app);
+        if data.exists() {
+            std::fs::create_dir_all(old.parent().unwrap())?;
+            std::fs::rename(&data, &old)?;
+        }
+        std::fs::create_dir_all(data.parent().unwrap())?;
+        if let Err(e) = std::fs::rename(&new, &data) {
+            if old.exists() {
+                _ = std::fs::rename(&old, &data);
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+struct Row {
+    id: String,
+    label: String,
+    value: String,
+}
+
+pub struct BackupScreen {
+    settings: Settings,
+    /// Apps with data and whether they are selected for export
+    export: Vec<(String, bool)>,
+    archives: Vec<PathBuf>,
+    /// The archive picked for import and its apps
+    import: Option<(PathBuf, Vec<(String, Resolution)>)>,
+    /// `author/app` of the app paused underneath, which must not get data imported
+    running: Option<String>,
+    status: String,
+    ui: ui::Renderer,
+}
+
+impl BackupScreen {
+    pub fn new(running: Option<&FullID>) -> Self {
+        let settings = Settings::load();
+        BackupScreen {
+            export: apps_with_data().into_iter().map(|a| (a, true)).collect(),
+            archives: archives(&backups_dir(&settings)),
+            import: None,
+            running: running.map(|id| format!("{}/{}", id.author(), id.app())),
+            status: String::new(),
+            ui: ui::Renderer::new(safe_area::insets(&settings)),
+            settings,
+        }
+    }
+
+    fn rows(&self) -> Vec<Row> {
+        let settings = &self.settings;
+        let row = |id: String, label: &str, value: &str| Row {
+            id,
+            label: label.to_owned(),
+            value: value.to_owned(),
+        };
+        let mut rows = vec![];
+        for (i, (app, selected)) in self.export.iter().enumerate() {
+            rows.push(row(
+                format!("select:{i}"),
+                app,
+                if *selected { "[x]" } else { "[ ]" },
+            ));
+        }
+        rows.push(row(
+            "export".to_owned(),
+            settings.tr(Text::ExportSelected),
+            "",
+        ));
+        for (i, path) in self.archives.iter().enumerate() {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            rows.push(row(
+                format!("archive:{i}"),
+                settings.tr(Text::Backup),
+                &name,
+            ));
+        }
+        if let Some((_, apps)) = &self.import {
+            for (i, (app, resolution)) in apps.iter().enumerate() {
+                let value = if self.running.as_ref() == Some(app) {
+                    Text::InUse
+                } else {
+                    resolution.text()
+                };
+                rows.push(row(format!("resolve:{i}"), app, settings.tr(value)));
+            }
+            rows.push(row(
+                "import".to_owned(),
+                settings.tr(Text::ImportSelected),
+                "",
+            ));
+        }
+        rows
+    }
+
+    pub async fn show(&mut self) -> Nav {
+        loop {
+            if nav::back_pressed() {
+                return Nav::Pop;
+            }
+            let theme = self.settings.theme;
+            self.ui.insets = safe_area::insets(&self.settings);
+            clear_background(theme.background());
+            let rows = self.rows();
+            let title = self.settings.tr(Text::Backup);
+            let status = &self.status;
+            let label_width = safe_area::rect(&self.settings).w as f64 / 2.;
+            self.ui.draw(|k| {
+                let style = TextStyle::new()
+                    .font_size(DESCR_FONT_SIZE)
+                    .color(theme.foreground().into());
+                let border = || Border {
+                    width: 3.,
+                    color: theme.border().into(),
+                };
+                k.styled(
+                    FlexStyle::new()
+                        .background_color(theme.background().into())
+                        .layout(Layout::new().direction(Direction::TopToBottom))
+                        .sizing(sizing!(grow!())),
+                    |mut k| {
+                        k = k.styled(
+                            FlexStyle::new()
+                                .background_color(theme.header().into())
+                                .layout(
+                                    Layout::new()
+                                        .alignment(Alignment::Center)
+                                        .justification(Justification::Center),
+                                )
+                                .sizing(sizing!(grow!(), fit!())),
+                            |k| k.text(title, style.font_size(TITLE_FONT_SIZE)),
+                        );
+                        k = k.text(status, style);
+                        for row in &rows {
+                            k = k.styled(
+                                FlexStyle::new()
+                                    .custom(row.id.as_str())
+                                    .border(border())
+                                    .layout(Layout::new().direction(Direction::LeftToRight))
+                                    .sizing(sizing!(grow!(), fit!())),
+                                |k| {
+                                    k.styled(
+                                        FlexStyle::new()
+                                            .border(border())
+                                            .sizing(sizing!(fixed!(label_width), grow!())),
+                                        |k| k.text(&row.label, style),
+                                    )
+                                    .styled(
+                                        FlexStyle::new().border(border()).sizing(sizing!(grow!())),
+                                        |k| {
+                                            k.text(
+                                                &row.value,
+                                                style.font_size(BUTTON_FONT_SIZE / 2.),
+                                            )
+                                        },
+                                    )
+                                },
+                            );
+                        }
+                        k
+                    },
+                )
+            });
+
+            if let Some(id) = self.ui.clicked.iter().next().cloned() {
+                self.click(&id);
+            }
+            next_frame().await;
+        }
+    }
+
+    fn click(&mut self, id: &str) {
+        let settings = &self.settings;
+        let result = match id.split_once(':') {
+            Some(("select", i)) => {
+                let selected = &mut self.export[i.parse::<usize>().unwrap()].1;
+                *selected = !*selected;
+                return;
+            }
+            Some(("archive", i)) => {
+                let path = self.archives[i.parse::<usize>().unwrap()].clone();
+                apps_in(&path).map(|mut apps| {
+                    // It would write over the imported data while running.
+                    for (app, resolution) in &mut apps {
+                        if self.running.as_ref() == Some(app) {
+                            *resolution = Resolution::Skip;
+                        }
+                    }
+                    self.import = Some((path, apps));
+                    String::new()
+                })
+            }
+            Some(("resolve", i)) => {
+                let (_, apps) = self.import.as_mut().unwrap();
+                let (app, resolution) = &mut apps[i.parse::<usize>().unwrap()];
+                if self.running.as_ref() != Some(app) {
+                    *resolution = resolution.next();
+                }
+                return;
+            }
+            _ if id == "export" => {
+                let apps: Vec<_> = self
+                    .export
+                    .iter()
+                    .filter(|(_, selected)| *selected)
+                    .map(|(app, _)| app.clone())
+                    .collect();
+                let backups = backups_dir(settings);
+                export(&backups, &apps).and_then(|path| {
+                    self.archives = archives(&backups);
+                    let place = publish(&path)?;
+                    Ok(format!("{} {place}", settings.tr(Text::SavedTo)))
+                })
+            }
+            _ if id == "import" => {
+                let (path, apps) = self.import.take().unwrap();
+                import(&path, &apps).map(|()| {
+                    self.export = apps_with_data().into_iter().map(|a| (a, true)).collect();
+                    settings.tr(Text::Imported).to_owned()
+                })
+            }
+            _ => panic!("{id}"),
+        };
+        self.status = result.unwrap_or_else(|e| e.to_string());
+    }
+}
@@ -5,13 +5,15 @@ use macroquad::prelude::*;
 use miniserde::Deserialize;
 
 use crate::{
+    achievements::AchievementsScreen,
     dir,
-    ui::{self},
+    i18n::Text,
+    nav::{self, Nav, Screen},
+    runtime_log, safe_area,
+    settings::{Settings, SettingsScreen},
+    ui::{self, BUTTON_FONT_SIZE, DESCR_FONT_SIZE, TITLE_FONT_SIZE},
 };
 
-const BASE_URL: &str = "https://catalog.fireflyzero.com/";
-const LIST_URL: &str = "https://catalog.fireflyzero.com/apps.json";
-
 #[derive(Deserialize)]
 struct ShortApp {
     id: String,
@@ -27,253 +29,401 @@ struct App {
     categories: Vec<String>,
 }
 
-const TITLE_FONT_SIZE: f32 = if cfg!(target_os = "android") {
-    150.
-} else {
-    80.
-};
-const BUTTON_FONT_SIZE: f32 = if cfg!(target_os = "android") {
-    120.
-} else {
-    50.
-};
-const DESCR_FONT_SIZE: f32 = if cfg!(target_os = "android") {
-    80.
-} else {
-    30.
-};
+/// Download and parse a JSON file from the catalog.
+fn fetch<T: Deserialize>(url: &str) -> Result<T, String> {
+    let resp = ureq::get(url).call().map_err(|e| e.to_string())?;
+    let body = resp
+        .into_body()
+        .read_to_string()
+        .map_err(|e| e.to_string())?;
+    miniserde::json::from_str(&body).map_err(|e| e.to_string())
+}
 
-pub async fn list() {
-    let resp = match ureq::get(LIST_URL).call() {
-        Ok(r) => r,
-        Err(e) => {
-            while !is_key_down(KeyCode::Back) {
-                draw_text(&format!("{e:?}"), 0., 0., 50., RED);
-                next_frame().await;
-            }
-            return;
-        }
-    };
-    let body = match resp.into_body().read_to_string() {
-        Ok(body) => body,
-        Err(_) => todo!(),
-    };
-    let apps: Vec<ShortApp> = match miniserde::json::from_str(&body) {
-        Ok(apps) => apps,
-        Err(_) => todo!(),
-    };
-    let mut ui = ui::Renderer::new();
-    let name_width = apps
-        .iter()
+fn max_name_width(apps: &[ShortApp], settings: &Settings) -> f64 {
+    apps.iter()
         .map(|app| measure_text(&app.name, None, DESCR_FONT_SIZE as _, 1.).width as u32)
         .max()
-        .unwrap()
-        .min(screen_width() as u32 / 3) as f64;
-    while !is_key_pressed(KeyCode::Escape) {
-        clear_background(GRAY);
-        ui.draw(|k| {
-            let style = TextStyle::new()
-                .font_size(DESCR_FONT_SIZE)
-                .color(BLACK.into());
-            k.styled(
-                FlexStyle::new()
-                    .background_color(GRAY.into())
-                    .layout(Layout::new().direction(Direction::TopToBottom))
-                    .sizing(sizing!(grow!())),
-                |k| {
-                    k.styled(
-                        FlexStyle::new()
-                            .background_color(WHITE.into())
-                            .layout(
-                                Layout::new()
-                                    .alignment(Alignment::Center)
-                                    .justification(Justification::Center),
-                            )
-                            .sizing(sizing!(grow!(), fit!())),
-                        |k| {
-                            k.text(
-                                "Catalog",
-                                TextStyle::new()
-                                    .font_size(TITLE_FONT_SIZE)
-                                    .color(BLACK.into()),
-                            )
-                        },
-                    )
-                    .styled(
-                        FlexStyle::new()
-                            .background_color(GRAY.into())
-                            .layout(Layout::new().direction(Direction::TopToBottom))
-                            .sizing(sizing!(grow!())),
-                        |mut k| {
-                            for app in &apps {
-                                k = k.styled(
-                                    FlexStyle::new()
-                                        .custom(app.id.as_str())
-                                        .background_color(GRAY.into())
-                                        .border(Border {
-                                            width: 3.,
-                                            color: DARKGRAY.into(),
-                                        })
-                                        .layout(Layout::new().direction(Direction::LeftToRight))
-                                        .sizing(sizing!(grow!(), fit!())),
-                                    |k| {
-                                        k.styled(
-                                            FlexStyle::new()
-                                                .border(Border {
-                                                    width: 3.,
-                                                    color: DARKGRAY.into(),
-                                                })
-                                                .sizing(sizing!(fixed!(name_width), grow!())),
-                                            |k| k.text(&app.name, style),
-                                        )
-                                        .styled(
-                                            FlexStyle::new()
-                                                .border(Border {
-                                                    width: 3.,
-                                                    color: DARKGRAY.into(),
-                                                })
-                                                .background_color(GRAY.into())
-                                                .sizing(sizing!(grow!())),
-                                            |k| k.text(&app.short, style),
-                                        )
-                                    },
-                                );
-                            }
-                            k
-                        },
-                    )
-                },
-            )
-        });
-
-        if let Some(id) = ui.clicked.iter().next() {
-            // clear clicks
-            next_frame().await;
-            // render app info
-            app(id).await;
-            if is_key_down(KeyCode::Escape) {
-                return;
-            }
-        }
+        .unwrap_or_default()
+        .min(safe_area::rect(settings).w as u32 / 3) as f64
+}
 
-        next_frame().await
-    }
+pub struct ListScreen {
+    settings: Settings,
+    /// The apps in the catalog, or why they couldn't be loaded
+    apps: Result<Vec<ShortApp>, String>,
+    name_width: f64,
+    ui: ui::Renderer,
 }
 
-pub async fn app(id: &str) {
-    let url = format!("{BASE_URL}{id}.json");
-    let resp = match ureq::get(&url).call() {
-        Ok(r) => r,
-        Err(_) => todo!(),
-    };
-    let body = match resp.into_body().read_to_string() {
-        Ok(body) => body,
-        Err(_) => todo!(),
-    };
-    let app: App = match miniserde::json::from_str(&body) {
-        Ok(app) => app,
-        Err(_) => todo!(),
-    };
+impl ListScreen {
+    /// Load the list of apps.
+    ///
+    /// If that fails, the screen shows the error instead, so that the catalog URL
+    /// can be fixed in the settings without restarting.
+    pub fn new() -> Self {
+        let settings = Settings::load();
+        let apps = fetch(&format!("{}apps.json", settings.catalog_url));
+        if let Err(e) = &apps {
+            runtime_log::record(format!("failed to load the catalog: {e}"));
+        }
+        ListScreen {
+            name_width: apps
+                .as_ref()
+                .map_or(0., |apps| max_name_width(apps, &settings)),
+            ui: ui::Renderer::new(safe_area::insets(&settings)),
+            settings,
+            apps,
+        }
+    }
 
-    let id = FullID::try_from(id).unwrap_or_else(|e| panic!("{}", e));
+    pub async fn show(&mut self) -> Nav {
+        // The settings may have changed while another screen was open.
+        let settings = Settings::load();
+        if settings.catalog_url != self.settings.catalog_url {
+            return Nav::Replace(Screen::Catalog(ListScreen::new()));
+        }
+        if let Ok(apps) = &self.apps {
+            self.name_width = max_name_width(apps, &settings);
+        }
+        self.settings = settings;
 
-    let cache = dir().join("roms").join(id.author()).join(id.app());
+        let settings = &self.settings;
+        let apps = &self.apps;
+        let name_width = self.name_width;
+        let ui = &mut self.ui;
+        let error = match apps {
+            Ok(_) => vec![],
+            Err(e) => ui::wrap(
+                &format!("{}: {e}", settings.tr(Text::CatalogUnavailable)),
+                DESCR_FONT_SIZE,
+                safe_area::rect(settings).w,
+            ),
+        };
+        let mut toast: Option<ui::Toast> = None;
+        loop {
+            if nav::back_pressed() {
+                return Nav::Pop;
+            }
+            let theme = settings.theme;
+            ui.insets = safe_area::insets(settings);
+            clear_background(theme.background());
+            ui.draw(|k| {
+                let style = TextStyle::new()
+                    .font_size(DESCR_FONT_SIZE)
+                    .color(theme.foreground().into());
+                let border = || Border {
+                    width: 3.,
+                    color: theme.border().into(),
+                };
+                k.styled(
+                    FlexStyle::new()
+                        .background_color(theme.background().into())
+                        .layout(Layout::new().direction(Direction::TopToBottom))
+                        .sizing(sizing!(grow!())),
+                    |k| {
+                        k.styled(
+                            FlexStyle::new()
+                                .background_color(theme.header().into())
+                                .layout(
+                                    Layout::new()
+                                        .alignment(Alignment::Center)
+                                        .justification(Justification::Center)
+                                        .gap(20.),
+                                )
+                                .sizing(sizing!(grow!(), fit!())),
+                            |k| {
+                                k.text(settings.tr(Text::Catalog), style.font_size(TITLE_FONT_SIZE))
+                                    .styled(
+                                        FlexStyle::new().custom("launcher").border(border()),
+                                        |k| {
+                                            k.text(
+                                                settings.tr(Text::Launcher),
+                                                style.font_size(BUTTON_FONT_SIZE),
+                                            )
+                                        },
+                                    )
+                                    .styled(
+                                        FlexStyle::new().custom("settings").border(border()),
+                                        |k| {
+                                            k.text(
+                                                settings.tr(Text::Settings),
+                                                style.font_size(BUTTON_FONT_SIZE),
+                                            )
+                                        },
+                                    )
+                            },
+                        )
+                        .styled(
+                            FlexStyle::new()
+                                .background_color(theme.background().into())
+                                .layout(Layout::new().direction(Direction::TopToBottom))
+                                .sizing(sizing!(grow!())),
+                            |mut k| {
+                                for line in &error {
+                                    k = k.text(line, style);
+                                }
+                                if apps.is_err() {
+                                    k = k.styled(
+                                        FlexStyle::new()
+                                            .custom("retry")
+                                            .border(border())
+                                            .layout(
+                                                Layout::new().justification(Justification::Center),
+                                            )
+                                            .sizing(sizing!(grow!(), fit!())),
+                                        |k| {
+                                            k.text(
+                                                settings.tr(Text::Retry),
+                                                style.font_size(BUTTON_FONT_SIZE),
+                                            )
+                                        },
+                                    );
+                                }
+                                for app in apps.iter().flatten() {
+                                    k = k.styled(
+                                        FlexStyle::new()
+                                            .custom(app.id.as_str())
+                                            .background_color(theme.background().into())
+                                            .border(border())
+                                            .layout(Layout::new().direction(Direction::LeftToRight))
+                                            .sizing(sizing!(grow!(), fit!())),
+                                        |k| {
+                                            k.styled(
+                                                FlexStyle::new()
+                                                    .border(border())
+                                                    .sizing(sizing!(fixed!(name_width), grow!())),
+                                                |k| k.text(&app.name, style),
+                                            )
+                                            .styled(
+                                                FlexStyle::new()
+                                                    .border(border())
+                                                    .background_color(theme.background().into())
+                                                    .sizing(sizing!(grow!())),
+                                                |k| k.text(&app.short, style),
+                                            )
+                                        },
+                                    );
+                                }
+                                k
+                            },
+                        )
+                    },
+                )
+            });
 
-    let mut ui = ui::Renderer::new();
-    while !is_key_pressed(KeyCode::Back) && !is_key_pressed(KeyCode::Escape) {
-        clear_background(GRAY);
-        ui.draw(|k| {
-            let style = TextStyle::new()
-                .font_size(DESCR_FONT_SIZE)
-                .color(BLACK.into());
-            k.styled(
-                FlexStyle::new()
-                    .background_color(GRAY.into())
-                    .layout(Layout::new().direction(Direction::TopToBottom).gap(20.))
-                    .sizing(sizing!(grow!())),
-                |mut k| {
-                    let action = if cache.exists() { "Run" } else { "Download" };
-                    k = k.text(
-                        &app.name,
-                        TextStyle::new()
-                            .font_size(TITLE_FONT_SIZE)
-                            .color(BLACK.into()),
-                    );
-                    k = k.text(&app.desc, style);
-                    k = k.styled(
-                        FlexStyle::new()
-                            .border(Border {
-                                width: 10.,
-                                color: DARKGREEN.into(),
-                            })
-                            .layout(Layout::new().justification(Justification::Center))
-                            .sizing(sizing!(grow!(), fit!()))
-                            .custom(action),
-                        |k| {
-                            k.text(
-                                action,
-                                style.font_size(BUTTON_FONT_SIZE).color(GREEN.into()),
-                            )
-                        },
-                    );
-                    for cat in &app.categories {
-                        k = k.text(cat, style);
+            match ui.clicked.iter().next().map(|s| s.as_str()) {
+                Some("settings") => return Nav::Push(Screen::Settings(SettingsScreen::new())),
+                Some("launcher") => return Nav::Push(Screen::Play(None)),
+                Some("retry") => return Nav::Replace(Screen::Catalog(ListScreen::new())),
+                Some(id) => match AppScreen::new(id, settings) {
+                    Ok(app) => return Nav::Push(Screen::App(app)),
+                    Err(e) => {
+                        let text = format!("{}: {e}", settings.tr(Text::AppUnavailable));
+                        toast = Some(ui::Toast::new(text));
                     }
-                    k
                 },
-            )
-        });
-        match ui.clicked.iter().next().map(|s| s.as_str()) {
-            Some("Download") => {
-                let resp = match ureq::get(&app.download).call() {
-                    Ok(r) => r,
-                    Err(_) => todo!(),
-                };
-                let body = match resp.into_body().read_to_vec() {
-                    Ok(body) => body,
-                    Err(_) => todo!(),
-                };
+                None => {}
+            }
+            if toast.as_ref().is_some_and(|t| !t.draw()) {
+                toast = None;
+            }
 
-                std::fs::create_dir_all(&cache).unwrap();
-                let data = dir().join("data").join(id.author()).join(id.app());
-                std::fs::create_dir_all(&data).unwrap();
-                let today = (1, 2, 3);
-                let stats = firefly_types::Stats {
-                    minutes: [0; 4],
-                    longest_play: [0; 4],
-                    launches: [0; 4],
-                    installed_on: today,
-                    updated_on: today,
-                    launched_on: (0, 0, 0),
-                    xp: 0,
-                    badges: Box::new([]),
-                    scores: Box::new([]),
-                };
-                let raw = stats.encode_vec().unwrap();
-                std::fs::write(data.join("stats"), raw).unwrap();
+            next_frame().await
+        }
+    }
+}
+
+pub struct AppScreen {
+    id: FullID,
+    app: App,
+    settings: Settings,
+    ui: ui::Renderer,
+}
 
-                let mut archive = zip::ZipArchive::new(std::io::Cursor::new(&body[..])).unwrap();
-                archive.extract(&cache).unwrap();
+impl AppScreen {
+    fn new(id: &str, settings: &Settings) -> Result<Self, String> {
+        let app: App = fetch(&format!("{}{id}.json", settings.catalog_url))?;
+        let id = FullID::try_from(id).map_err(|e| e.to_string())?;
+        Ok(AppScreen {
+            id,
+            app,
+            settings: settings.clone(),
+            ui: ui::Renderer::new(safe_area::insets(settings)),
+        })
+    }
+
+    pub async fn show(&mut self) -> Nav {
+        let AppScreen {
+            id,
+            app,
+            settings,
+            ui,
+        } = self;
+        let cache = dir().join("roms").join(id.author()).join(id.app());
+        let mut stats = stats_lines(id, settings);
+
+        let theme = settings.theme;
+        let mut toast: Option<ui::Toast> = None;
+        loop {
+            if nav::back_pressed() {
+                return Nav::Pop;
             }
-            Some("Run") => match crate::play(&id).await {
-                Ok(()) => {
-                    if is_key_pressed(KeyCode::Escape) {
-                        return;
+            ui.insets = safe_area::insets(settings);
+            clear_background(theme.background());
+            ui.draw(|k| {
+                let style = TextStyle::new()
+                    .font_size(DESCR_FONT_SIZE)
+                    .color(theme.foreground().into());
+                k.styled(
+                    FlexStyle::new()
+                        .background_color(theme.background().into())
+                        .layout(Layout::new().direction(Direction::TopToBottom).gap(20.))
+                        .sizing(sizing!(grow!())),
+                    |mut k| {
+                        let (action, label) = if cache.exists() {
+                            ("run", Text::Run)
+                        } else {
+                            ("download", Text::Download)
+                        };
+                        k = k.text(&app.name, style.font_size(TITLE_FONT_SIZE));
+                        k = k.text(&app.desc, style);
+                        k = k.styled(
+                            FlexStyle::new()
+                                .border(Border {
+                                    width: 10.,
+                                    color: DARKGREEN.into(),
+                                })
+                                .layout(Layout::new().justification(Justification::Center))
+                                .sizing(sizing!(grow!(), fit!()))
+                                .custom(action),
+                            |k| {
+                                k.text(
+                                    settings.tr(label),
+                                    style.font_size(BUTTON_FONT_SIZE).color(GREEN.into()),
+                                )
+                            },
+                        );
+                        if cache.exists() {
+                            k = k.styled(
+                                FlexStyle::new()
+                                    .border(Border {
+                                        width: 5.,
+                                        color: theme.border().into(),
+                                    })
+                                    .layout(Layout::new().justification(Justification::Center))
+                                    .sizing(sizing!(grow!(), fit!()))
+                                    .custom("achievements"),
+                                |k| {
+                                    k.text(
+                                        settings.tr(Text::Achievements),
+                                        style.font_size(BUTTON_FONT_SIZE / 2.),
+                                    )
+                                },
+                            );
+                        }
+                        for line in &stats {
+                            k = k.text(line, style);
+                        }
+                        for cat in &app.categories {
+                            k = k.text(cat, style);
+                        }
+                        k
+                    },
+                )
+            });
+            match ui.clicked.iter().next().map(|s| s.as_str()) {
+                Some("download") => match download(&app.download, id) {
+                    Ok(()) => stats = stats_lines(id, settings),
+                    Err(e) => {
+                        let text = format!("{}: {e}", settings.tr(Text::DownloadFailed));
+                        toast = Some(ui::Toast::new(text));
                     }
-                }
-                Err(e) => loop {
-                    clear_background(WHITE);
-                    draw_text(&e.to_string(), 0., 100., 30., BLACK);
-                    next_frame().await
                 },
-            },
-            Some(other) => panic!("{other}"),
-            None => {}
+                Some("run") => return Nav::Push(Screen::Play(Some(id.clone()))),
+                Some("achievements") => {
+                    let screen = AchievementsScreen::new(id, settings);
+                    return Nav::Push(Screen::Achievements(screen));
+                }
+                Some(other) => panic!("{other}"),
+                None => {}
+            }
+            if toast.as_ref().is_some_and(|t| !t.draw()) {
+                toast = None;
+            }
+            next_frame().await;
         }
-        next_frame().await;
     }
 }
 
+fn sum<T: Copy + Into<u64>>(values: &[T]) -> u64 {
+    values.iter().map(|v| (*v).into()).sum()
+}
+
+/// Play statistics of the installed app, summed over all player counts.
+fn stats_lines(id: &FullID, settings: &Settings) -> Vec<String> {
+    let Some(stats) = crate::stats::load(id) else {
+        return vec![];
+    };
+    let minutes = settings.tr(Text::Minutes);
+    let mut lines = vec![
+        format!("{}: {}", settings.tr(Text::Launches), sum(&stats.launches)),
+        format!(
+            "{}: {} {minutes}",
+            settings.tr(Text::PlayTime),
+            sum(&stats.minutes)
+        ),
+        format!(
+            "{}: {} {minutes}",
+            settings.tr(Text::LongestPlay),
+            stats.longest_play.iter().max().copied().unwrap_or_default()
+        ),
+    ];
+    let (year, month, day) = stats.launched_on;
+    if year != 0 {
+        lines.push(format!(
+            "{}: {year:04}-{month:02}-{day:02}",
+            settings.tr(Text::LastPlayed)
+        ));
+    }
+    lines
+}
+
+/// Download the ROM archive of the app and install it.
+fn download(url: &str, id: &FullID) -> Result<(), String> {
+    let resp = ureq::get(url).call().map_err(|e| e.to_string())?;
+    let body = resp.into_body().read_to_vec().map_err(|e| e.to_string())?;
+    install(id, &body).map_err(|e| e.to_string())
+}
+
+/// Unpack the ROM archive of the app and create its data directory.
+pub fn install(id: &FullID, archive: &[u8]) -> std::io::Result<()> {
+    let rom = dir().join("roms").join(id.author()).join(id.app());
+    std::fs::create_dir_all(&rom)?;
+    let data = dir().join("data").join(id.author()).join(id.app());
+    std::fs::create_dir_all(&data)?;
+    // Keep the stats of an earlier install when updating or reinstalling.
+    if !data.join("stats").exists() {
+        let today = crate::stats::today();
+        let stats = firefly_types::Stats {
+            minutes: [0; 4],
+            longest_play: [0; 4],
+            launches: [0; 4],
+            installed_on: today,
+            updated_on: today,
+            launched_on: (0, 0, 0),
+            xp: 0,
+            badges: Box::new([]),
+            scores: Box::new([]),
+        };
+        let raw = stats.encode_vec().map_err(std::io::Error::other)?;
+        std::fs::write(data.join("stats"), raw)?;
+    }
+
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(archive))?;
+    archive.extract(&rom)?;
+    Ok(())
+}
+
 /*
 pub fn show_author(args: &CatalogShowArgs) -> Result<()> {
     let url = format!("{BASE_URL}{}.json", args.id);
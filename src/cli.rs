@@ -0,0 +1,165 @@
+//! Command line arguments, for running the emulator from scripts.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use firefly_runtime::FullID;
+use firefly_types::Encode as _;
+
+use crate::{catalog, dir, lan};
+
+pub struct Args {
+    /// Use this directory for apps and their data instead of the default one
+    pub root: Option<PathBuf>,
+    pub command: Command,
+}
+
+pub enum Command {
+    /// Open the window, starting at the catalog
+    Gui,
+    /// Open the window, starting the app, or the launcher without an ID, right away
+    Run(Option<FullID>),
+    /// Install an app from a ROM archive
+    Install(PathBuf),
+    /// Print the installed apps
+    List,
+    Headless(Headless),
+    /// Look for other instances on the network for this many seconds
+    Peers(u64),
+}
+
+/// Run an app without a window and dump its state afterwards.
+pub struct Headless {
+    /// `author.app`
+    pub id: String,
+    /// How many updates to run
+    pub frames: u32,
+    /// A replay file (`.json`) or an input script (anything else)
+    pub input: Option<PathBuf>,
+    /// Where to write the final screen and the app data
+    pub out: PathBuf,
+    /// Also write the screen after each of these frames
+    pub dump: Vec<u32>,
+}
+
+const USAGE: &str = "usage:
+    fireflydroid [--root <dir>]
+    fireflydroid [--root <dir>] run [<author.app>]
+    fireflydroid [--root <dir>] install <path.zip>
+    fireflydroid [--root <dir>] list
+    fireflydroid [--root <dir>] peers <seconds>
+    fireflydroid [--root <dir>] --headless <author.app> --frames <n> [--input <replay.json|script.txt>] [--out <dir>] [--dump <frame,...>]";
+
+pub fn parse() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut root = None;
+    let mut words = vec![];
+    let mut id = None;
+    let mut frames = None;
+    let mut input = None;
+    let mut out = PathBuf::from(".");
+    let mut dump = vec![];
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--root" => root = Some(PathBuf::from(value()?)),
+            "--headless" => id = Some(value()?),
+            "--frames" => {
+                let n = value()?;
+                frames = Some(n.parse().map_err(|_| format!("invalid frame count {n}"))?);
+            }
+            "--input" => input = Some(PathBuf::from(value()?)),
+            "--out" => out = PathBuf::from(value()?),
+            "--dump" => {
+                for frame in value()?.split(',') {
+                    dump.push(
+                        frame
+                            .parse()
+                            .map_err(|_| format!("invalid frame {frame}"))?,
+                    );
+                }
+            }
+            _ if arg.starts_with('-') => return Err(USAGE.to_owned()),
+            _ => words.push(arg),
+        }
+    }
+    let command = match (id, &words[..]) {
+        (Some(id), []) => Command::Headless(Headless {
+            id,
+            frames: frames.ok_or(USAGE)?,
+            input,
+            out,
+            dump,
+        }),
+        (None, []) => Command::Gui,
+        (None, [cmd]) if cmd == "run" => Command::Run(None),
+        (None, [cmd, id]) if cmd == "run" => Command::Run(Some(parse_id(id)?)),
+        (None, [cmd, path]) if cmd == "install" => Command::Install(PathBuf::from(path)),
+        (None, [cmd]) if cmd == "list" => Command::List,
+        (None, [cmd, secs]) if cmd == "peers" => Command::Peers(
+            secs.parse()
+                .map_err(|_| format!("invalid duration {secs}"))?,
+        ),
+        _ => return Err(USAGE.to_owned()),
+    };
+    Ok(Args { root, command })
+}
+
+/// Install the app from the ROM archive, taking its ID from the metadata inside.
+pub fn install(path: &Path) -> Result<FullID, String> {
+    let archive = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(&archive[..]))
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+    let mut raw = vec![];
+    std::io::Read::read_to_end(
+        &mut zip.by_name("_meta").map_err(|e| format!("_meta: {e}"))?,
+        &mut raw,
+    )
+    .map_err(|e| format!("_meta: {e}"))?;
+    let meta = firefly_types::Meta::decode(&raw).map_err(|e| format!("_meta: {e}"))?;
+    let id = parse_id(&format!("{}.{}", meta.author_id, meta.app_id))?;
+    catalog::install(&id, &archive).map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// IDs of all installed apps, sorted.
+pub fn list() -> Result<Vec<String>, String> {
+    let roms = dir().join("roms");
+    if !roms.exists() {
+        return Ok(vec![]);
+    }
+    let mut ids = vec![];
+    for author in std::fs::read_dir(&roms).map_err(|e| e.to_string())? {
+        let author = author.map_err(|e| e.to_string())?;
+        for app in std::fs::read_dir(author.path()).map_err(|e| e.to_string())? {
+            let app = app.map_err(|e| e.to_string())?;
+            ids.push(format!(
+                "{}.{}",
+                author.file_name().to_string_lossy(),
+                app.file_name().to_string_lossy()
+            ));
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+/// Announce this instance and listen for others for a while.
+///
+/// Returns the own port, where the others announced themselves from,
+/// and the peers a device would get.
+pub fn peers(secs: u64) -> Result<(u16, Vec<SocketAddr>, Vec<IpAddr>), String> {
+    let lan = lan::start().map_err(|e| e.to_string())?;
+    std::thread::sleep(Duration::from_secs(secs));
+    Ok((lan.port, lan::instances(), lan::peers()))
+}
+
+fn parse_id(id: &str) -> Result<FullID, String> {
+    FullID::try_from(id).map_err(|e| format!("{id}: {e}"))
+}
@@ -0,0 +1,114 @@
+//! Error report shown after a panic.
+
+use std::path::PathBuf;
+
+use kaolin::prelude::*;
+use macroquad::{miniquad::window::clipboard_set, prelude::*};
+
+use crate::{
+    dir,
+    i18n::Text,
+    nav, safe_area,
+    settings::Settings,
+    ui::{self, BUTTON_FONT_SIZE, DESCR_FONT_SIZE, TITLE_FONT_SIZE},
+};
+
+/// Show the panic message and backtrace until the user asks to go back to the catalog.
+pub async fn report(msg: &str, backtrace: &str) {
+    let settings = Settings::load();
+    let theme = settings.theme;
+    let report = format!("{msg}\n\n{backtrace}");
+    let font_size = DESCR_FONT_SIZE / 1.5;
+    let lines = ui::wrap(&report, font_size, safe_area::rect(&settings).w);
+    let mut status = String::new();
+    let mut ui = ui::Renderer::new(safe_area::insets(&settings));
+    loop {
+        if nav::back_pressed() {
+            // Don't let the key press leak into the catalog.
+            next_frame().await;
+            return;
+        }
+        clear_background(RED);
+        ui.draw(|k| {
+            let style = TextStyle::new()
+                .font_size(font_size)
+                .color(theme.foreground().into());
+            k.styled(
+                FlexStyle::new()
+                    .background_color(RED.into())
+                    .layout(Layout::new().direction(Direction::TopToBottom).gap(10.))
+                    .sizing(sizing!(grow!())),
+                |mut k| {
+                    k = k.text(
+                        settings.tr(Text::CrashTitle),
+                        style.font_size(TITLE_FONT_SIZE),
+                    );
+                    k = k.styled(
+                        FlexStyle::new()
+                            .layout(Layout::new().direction(Direction::LeftToRight).gap(10.))
+                            .sizing(sizing!(grow!(), fit!())),
+                        |mut k| {
+                            for (id, text) in [
+                                ("copy", Text::Copy),
+                                ("save", Text::Save),
+                                ("catalog", Text::BackToCatalog),
+                            ] {
+                                k = k.styled(
+                                    FlexStyle::new()
+                                        .custom(id)
+                                        .background_color(theme.header().into())
+                                        .border(Border {
+                                            width: 3.,
+                                            color: theme.border().into(),
+                                        })
+                                        .sizing(sizing!(grow!(), fit!())),
+                                    |k| {
+                                        k.text(settings.tr(text), style.font_size(BUTTON_FONT_SIZE))
+                                    },
+                                );
+                            }
+                            k
+                        },
+                    );
+                    k = k.text(&status, style);
+                    for line in &lines {
+                        k = k.text(line, style);
+                    }
+                    k
+                },
+            )
+        });
+
+        match ui.clicked.iter().next().map(|s| s.as_str()) {
+            Some("copy") => {
+                clipboard_set(&report);
+                status = settings.tr(Text::Copied).to_owned();
+            }
+            Some("save") => {
+                status = match save(&report) {
+                    Ok(path) => format!("{} {}", settings.tr(Text::SavedTo), path.display()),
+                    Err(e) => e.to_string(),
+                };
+            }
+            Some("catalog") => {
+                // clear clicks
+                next_frame().await;
+                return;
+            }
+            _ => {}
+        }
+        next_frame().await;
+    }
+}
+
+fn save(report: &str) -> std::io::Result<PathBuf> {
+    let reports = dir().join("crash_reports");
+    std::fs::create_dir_all(&reports)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = reports.join(format!("{now}.txt"));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
@@ -0,0 +1,98 @@
+//! Debug mode that stops the clock and runs one update per key press.
+
+use firefly_hal::InputState;
+use macroquad::prelude::*;
+
+use crate::{
+    i18n::Text,
+    input,
+    play::UiPos,
+    settings::{KeyBindings, Settings},
+};
+
+#[derive(Default)]
+pub struct FrameAdvance {
+    pub active: bool,
+    /// Input that is applied to every advanced frame instead of the live one
+    held: Option<InputState>,
+    /// Frames advanced since the mode was entered
+    frames: u32,
+}
+
+impl FrameAdvance {
+    /// Enter or leave the mode when the toggle key is pressed.
+    ///
+    /// Returns true if the mode was left, so the caller can reset its clock.
+    pub fn toggle(&mut self, keys: &KeyBindings) -> bool {
+        if !input::is_any_key_pressed(&keys.frame_advance) {
+            return false;
+        }
+        let was_active = self.active;
+        *self = FrameAdvance {
+            active: !was_active,
+            ..Default::default()
+        };
+        was_active
+    }
+
+    /// The input to give to the runtime, holding it if requested.
+    pub fn input(&mut self, keys: &KeyBindings, live: InputState) -> InputState {
+        if input::is_any_key_pressed(&keys.hold_input) {
+            self.held = match self.held {
+                Some(_) => None,
+                None => Some(live.clone()),
+            };
+        }
+        self.held.clone().unwrap_or(live)
+    }
+
+    /// Run a single update when the step key is pressed.
+    pub fn updates(&mut self, keys: &KeyBindings) -> u32 {
+        if input::is_any_key_pressed(&keys.step) {
+            self.frames += 1;
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Show the frame counter and the input of the next frame on top of the game.
+    pub fn draw(&self, ui: &UiPos, input: &InputState, settings: &Settings) {
+        let pad = match &input.pad {
+            Some(pad) => format!("{},{}", pad.x, pad.y),
+            None => "-".to_owned(),
+        };
+        let buttons: String = ["S", "E", "W", "N", "M"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                if input.buttons & (1 << i) != 0 {
+                    *name
+                } else {
+                    "."
+                }
+            })
+            .collect();
+        let mut status = format!(
+            "{} {} {} {pad} {buttons}",
+            settings.tr(Text::Frame),
+            self.frames,
+            settings.tr(Text::Pad),
+        );
+        if self.held.is_some() {
+            status.push(' ');
+            status.push_str(settings.tr(Text::Hold));
+        }
+
+        let font_size = ui.pause.h / 2.;
+        let y = ui.y + ui.size.y - font_size / 2.;
+        draw_rectangle(
+            ui.x,
+            y - font_size,
+            ui.size.x,
+            font_size * 1.5,
+            Color::new(0., 0., 0., 0.6),
+        );
+        draw_text(&status, ui.x + font_size / 2., y, font_size, YELLOW);
+    }
+}
@@ -0,0 +1,72 @@
+//! Running an app without a window, for regression tests on machines without a GPU.
+//!
+//! The app runs in a [`Sandbox`], so the installed data is never changed.
+
+use firefly_hal::{Device, DeviceConfig, DeviceImpl};
+use firefly_runtime::{FullID, NetHandler};
+
+use crate::{
+    cli::Headless,
+    copy_dir,
+    replay::{Replay, Sandbox},
+    screenshot::write_png,
+    HostState,
+};
+
+/// Run the app for the requested number of updates,
+/// then write the final screen and the app data into the output directory.
+///
+/// Screens of the frames listed in `dump` are written as `screen-<frame>.png` on the way.
+pub fn run(args: &Headless) -> Result<(), String> {
+    let id = FullID::try_from(args.id.as_str()).map_err(|e| e.to_string())?;
+    // A replay brings the data the app starts with, otherwise it's a copy of the current one.
+    let (mut input, sandbox) = match &args.input {
+        Some(path) if path.extension().is_some_and(|e| e == "json") => {
+            let (replay, sandbox) =
+                Replay::load(path, &id).map_err(|e| format!("{}: {e}", path.display()))?;
+            (Some(replay), sandbox)
+        }
+        Some(path) => {
+            let script =
+                std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+            let sandbox = Sandbox::new(&id, None).map_err(|e| e.to_string())?;
+            (Some(Replay::from_script(&script)?), sandbox)
+        }
+        None => (None, Sandbox::new(&id, None).map_err(|e| e.to_string())?),
+    };
+
+    let device = DeviceImpl::new(DeviceConfig {
+        root: sandbox.root().to_owned(),
+        ..DeviceConfig::default()
+    });
+    let config = firefly_runtime::RuntimeConfig {
+        id: Some(id.clone()),
+        device,
+        display: HostState::new(),
+        net_handler: NetHandler::None,
+    };
+    let mut runtime = firefly_runtime::Runtime::new(config).map_err(|e| e.to_string())?;
+    runtime.start().map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&args.out).map_err(|e| e.to_string())?;
+    for frame in 1..=args.frames {
+        let state = input.as_mut().and_then(Replay::next).unwrap_or_default();
+        runtime.device_mut().update_input(state);
+        if runtime
+            .update()
+            .map_err(|e| format!("frame {frame}: {e}"))?
+        {
+            eprintln!("the app exited after {frame} frames");
+            break;
+        }
+        if args.dump.contains(&frame) {
+            let path = args.out.join(format!("screen-{frame}.png"));
+            write_png(&runtime.display_mut().screen, &path).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let screen = args.out.join("screen.png");
+    write_png(&runtime.display_mut().screen, &screen).map_err(|e| e.to_string())?;
+    runtime.finalize().map_err(|e| e.to_string())?;
+    copy_dir(&sandbox.data_dir(&id), &args.out.join("data")).map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,393 @@
+//! Translations of all UI strings.
+//!
+//! To add a new language, add a variant to [`Language`] and a function
+//! translating every [`Text`] into it.
+
+use std::sync::OnceLock;
+
+use crate::settings::{Language, Scaling, Settings, Theme, TouchLayout};
+
+#[derive(Copy, Clone)]
+pub enum Text {
+    Catalog,
+    Settings,
+    Run,
+    Download,
+    CatalogUnavailable,
+    AppUnavailable,
+    DownloadFailed,
+    CatalogUrl,
+    Theme,
+    Volume,
+    AudioLatency,
+    Scaling,
+    TouchLayout,
+    Language,
+    Key,
+    PressAKey,
+    ResetKeys,
+    ActionLeft,
+    ActionRight,
+    ActionUp,
+    ActionDown,
+    ActionCenter,
+    ActionS,
+    ActionE,
+    ActionW,
+    ActionN,
+    ActionMenu,
+    ActionPause,
+    ActionSpeed,
+    ActionFrameAdvance,
+    ActionStep,
+    ActionHoldInput,
+    ActionScreenshot,
+    ActionRecord,
+    ThemeLight,
+    ThemeDark,
+    ScalingFit,
+    ScalingInteger,
+    PadLeft,
+    PadRight,
+    SystemLanguage,
+    SafeArea,
+    Auto,
+    Manual,
+    Top,
+    Right,
+    Bottom,
+    Left,
+    CrashTitle,
+    Copy,
+    Copied,
+    Save,
+    SavedTo,
+    BackToCatalog,
+    RuntimeError,
+    Retry,
+    Back,
+    Log,
+    Paused,
+    Resume,
+    Restart,
+    QuitToCatalog,
+    Quit,
+    Screenshot,
+    ScreenshotSaved,
+    ScreenshotScale,
+    Native,
+    StartRecording,
+    StopRecording,
+    RecordingSaved,
+    SavingRecording,
+    RecordingEmpty,
+    RecordInput,
+    StopRecordingInput,
+    InputSaved,
+    ReplayInput,
+    ReplayOver,
+    Frame,
+    Pad,
+    Hold,
+    Backup,
+    BackupDir,
+    ExportSelected,
+    ImportSelected,
+    Overwrite,
+    Merge,
+    Skip,
+    Imported,
+    InUse,
+    Launcher,
+    Launches,
+    PlayTime,
+    LongestPlay,
+    LastPlayed,
+    Minutes,
+    Achievements,
+    NoAchievements,
+    Xp,
+    Earned,
+    Locked,
+    NoScores,
+}
+
+impl Settings {
+    /// Translate the text into the language selected in the settings.
+    pub fn tr(&self, text: Text) -> &'static str {
+        match self.language.resolve() {
+            Language::System | Language::English => english(text),
+            Language::German => german(text),
+        }
+    }
+}
+
+impl Language {
+    /// Replace [`Language::System`] with the detected system language.
+    fn resolve(self) -> Self {
+        match self {
+            Language::System => *SYSTEM_LANGUAGE.get_or_init(system_language),
+            lang => lang,
+        }
+    }
+}
+
+impl Theme {
+    pub fn text(self) -> Text {
+        match self {
+            Theme::Light => Text::ThemeLight,
+            Theme::Dark => Text::ThemeDark,
+        }
+    }
+}
+
+impl Scaling {
+    pub fn text(self) -> Text {
+        match self {
+            Scaling::Fit => Text::ScalingFit,
+            Scaling::Integer => Text::ScalingInteger,
+        }
+    }
+}
+
+impl TouchLayout {
+    pub fn text(self) -> Text {
+        match self {
+            TouchLayout::PadLeft => Text::PadLeft,
+            TouchLayout::PadRight => Text::PadRight,
+        }
+    }
+}
+
+static SYSTEM_LANGUAGE: OnceLock<Language> = OnceLock::new();
+
+fn system_language() -> Language {
+    let locale = if cfg!(target_os = "android") {
+        std::process::Command::new("getprop")
+            .arg("persist.sys.locale")
+            .output()
+            .ok()
+            .and_then(|out| String::from_utf8(out.stdout).ok())
+    } else {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|val| !val.is_empty())
+    };
+    match locale.as_deref().map(|l| l.trim().get(..2).unwrap_or(l)) {
+        Some("de") => Language::German,
+        _ => Language::English,
+    }
+}
+
+fn english(text: Text) -> &'static str {
+    match text {
+        Text::Catalog => "Catalog",
+        Text::Settings => "Settings",
+        Text::Run => "Run",
+        Text::Download => "Download",
+        Text::CatalogUnavailable => "Can't load the catalog",
+        Text::AppUnavailable => "Can't load the app",
+        Text::DownloadFailed => "Download failed",
+        Text::CatalogUrl => "Catalog URL",
+        Text::Theme => "Theme",
+        Text::Volume => "Volume",
+        Text::AudioLatency => "Audio latency",
+        Text::Scaling => "Scaling",
+        Text::TouchLayout => "Touch layout",
+        Text::Language => "Language",
+        Text::Key => "Key",
+        Text::PressAKey => "press a key...",
+        Text::ResetKeys => "Reset keys",
+        Text::ActionLeft => "left",
+        Text::ActionRight => "right",
+        Text::ActionUp => "up",
+        Text::ActionDown => "down",
+        Text::ActionCenter => "center",
+        Text::ActionS => "S",
+        Text::ActionE => "E",
+        Text::ActionW => "W",
+        Text::ActionN => "N",
+        Text::ActionMenu => "menu",
+        Text::ActionPause => "pause",
+        Text::ActionSpeed => "speed",
+        Text::ActionFrameAdvance => "frame advance",
+        Text::ActionStep => "step",
+        Text::ActionHoldInput => "hold input",
+        Text::ActionScreenshot => "screenshot",
+        Text::ActionRecord => "record",
+        Text::ThemeLight => "Light",
+        Text::ThemeDark => "Dark",
+        Text::ScalingFit => "Fit",
+        Text::ScalingInteger => "Integer",
+        Text::PadLeft => "Pad on the left",
+        Text::PadRight => "Pad on the right",
+        Text::SystemLanguage => "System",
+        Text::SafeArea => "Safe area",
+        Text::Auto => "Auto",
+        Text::Manual => "Manual",
+        Text::Top => "top",
+        Text::Right => "right",
+        Text::Bottom => "bottom",
+        Text::Left => "left",
+        Text::CrashTitle => "Something went wrong",
+        Text::Copy => "Copy",
+        Text::Copied => "Copied to clipboard",
+        Text::Save => "Save",
+        Text::SavedTo => "Saved to",
+        Text::BackToCatalog => "Back to catalog",
+        Text::RuntimeError => "The app stopped with an error",
+        Text::Retry => "Retry",
+        Text::Back => "Back",
+        Text::Log => "Log:",
+        Text::Paused => "Paused",
+        Text::Resume => "Resume",
+        Text::Restart => "Restart",
+        Text::QuitToCatalog => "Quit to catalog",
+        Text::Quit => "Quit",
+        Text::Screenshot => "Screenshot",
+        Text::ScreenshotSaved => "Screenshot saved",
+        Text::ScreenshotScale => "Screenshot upscale",
+        Text::Native => "off",
+        Text::StartRecording => "Start recording",
+        Text::StopRecording => "Stop recording",
+        Text::RecordingSaved => "Recording saved",
+        Text::SavingRecording => "Saving recording...",
+        Text::RecordingEmpty => "Nothing was recorded",
+        Text::RecordInput => "Record input",
+        Text::StopRecordingInput => "Stop recording input",
+        Text::InputSaved => "Input recording saved",
+        Text::ReplayInput => "Replay last input recording",
+        Text::ReplayOver => "Replay finished",
+        Text::Frame => "frame",
+        Text::Pad => "pad",
+        Text::Hold => "hold",
+        Text::Backup => "Backup",
+        Text::BackupDir => "Backup folder",
+        Text::ExportSelected => "Export selected data",
+        Text::ImportSelected => "Import",
+        Text::Overwrite => "overwrite",
+        Text::Merge => "add missing files",
+        Text::Skip => "skip",
+        Text::Imported => "Data imported",
+        Text::InUse => "running, quit it first",
+        Text::Launcher => "Launcher",
+        Text::Launches => "Launches",
+        Text::PlayTime => "Play time",
+        Text::LongestPlay => "Longest session",
+        Text::LastPlayed => "Last played",
+        Text::Minutes => "min",
+        Text::Achievements => "Badges and scores",
+        Text::NoAchievements => "Nothing here yet",
+        Text::Xp => "XP",
+        Text::Earned => "earned",
+        Text::Locked => "hidden",
+        Text::NoScores => "no scores yet",
+    }
+}
+
+fn german(text: Text) -> &'static str {
+    match text {
+        Text::Catalog => "Katalog",
+        Text::Settings => "Einstellungen",
+        Text::Run => "Starten",
+        Text::Download => "Herunterladen",
+        Text::CatalogUnavailable => "Katalog kann nicht geladen werden",
+        Text::AppUnavailable => "App kann nicht geladen werden",
+        Text::DownloadFailed => "Herunterladen fehlgeschlagen",
+        Text::CatalogUrl => "Katalog-URL",
+        Text::Theme => "Design",
+        Text::Volume => "Lautstärke",
+        Text::AudioLatency => "Audio-Latenz",
+        Text::Scaling => "Skalierung",
+        Text::TouchLayout => "Touch-Layout",
+        Text::Language => "Sprache",
+        Text::Key => "Taste",
+        Text::PressAKey => "Taste drücken...",
+        Text::ResetKeys => "Tasten zurücksetzen",
+        Text::ActionLeft => "links",
+        Text::ActionRight => "rechts",
+        Text::ActionUp => "hoch",
+        Text::ActionDown => "runter",
+        Text::ActionCenter => "Mitte",
+        Text::ActionS => "S",
+        Text::ActionE => "E",
+        Text::ActionW => "W",
+        Text::ActionN => "N",
+        Text::ActionMenu => "Menü",
+        Text::ActionPause => "Pause",
+        Text::ActionSpeed => "Tempo",
+        Text::ActionFrameAdvance => "Einzelbildmodus",
+        Text::ActionStep => "Schritt",
+        Text::ActionHoldInput => "Eingabe halten",
+        Text::ActionScreenshot => "Bildschirmfoto",
+        Text::ActionRecord => "Aufnahme",
+        Text::ThemeLight => "Hell",
+        Text::ThemeDark => "Dunkel",
+        Text::ScalingFit => "Einpassen",
+        Text::ScalingInteger => "Ganzzahlig",
+        Text::PadLeft => "Pad links",
+        Text::PadRight => "Pad rechts",
+        Text::SystemLanguage => "System",
+        Text::SafeArea => "Sicherer Bereich",
+        Text::Auto => "Automatisch",
+        Text::Manual => "Manuell",
+        Text::Top => "oben",
+        Text::Right => "rechts",
+        Text::Bottom => "unten",
+        Text::Left => "links",
+        Text::CrashTitle => "Etwas ist schiefgelaufen",
+        Text::Copy => "Kopieren",
+        Text::Copied => "In die Zwischenablage kopiert",
+        Text::Save => "Speichern",
+        Text::SavedTo => "Gespeichert unter",
+        Text::BackToCatalog => "Zurück zum Katalog",
+        Text::RuntimeError => "Die App wurde mit einem Fehler beendet",
+        Text::Retry => "Erneut versuchen",
+        Text::Back => "Zurück",
+        Text::Log => "Protokoll:",
+        Text::Paused => "Pausiert",
+        Text::Resume => "Fortsetzen",
+        Text::Restart => "Neu starten",
+        Text::QuitToCatalog => "Zurück zum Katalog",
+        Text::Quit => "Beenden",
+        Text::Screenshot => "Bildschirmfoto",
+        Text::ScreenshotSaved => "Bildschirmfoto gespeichert",
+        Text::ScreenshotScale => "Bildschirmfoto vergrößern",
+        Text::Native => "aus",
+        Text::StartRecording => "Aufnahme starten",
+        Text::StopRecording => "Aufnahme beenden",
+        Text::RecordingSaved => "Aufnahme gespeichert",
+        Text::SavingRecording => "Aufnahme wird gespeichert...",
+        Text::RecordingEmpty => "Es wurde nichts aufgenommen",
+        Text::RecordInput => "Eingaben aufzeichnen",
+        Text::StopRecordingInput => "Eingabeaufzeichnung beenden",
+        Text::InputSaved => "Eingabeaufzeichnung gespeichert",
+        Text::ReplayInput => "Letzte Eingabeaufzeichnung abspielen",
+        Text::ReplayOver => "Wiedergabe beendet",
+        Text::Frame => "Bild",
+        Text::Pad => "Pad",
+        Text::Hold => "gehalten",
+        Text::Backup => "Sicherung",
+        Text::BackupDir => "Sicherungsordner",
+        Text::ExportSelected => "Ausgewählte Daten exportieren",
+        Text::ImportSelected => "Importieren",
+        Text::Overwrite => "überschreiben",
+        Text::Merge => "fehlende Dateien hinzufügen",
+        Text::Skip => "überspringen",
+        Text::Imported => "Daten importiert",
+        Text::InUse => "läuft, erst beenden",
+        Text::Launcher => "Launcher",
+        Text::Launches => "Starts",
+        Text::PlayTime => "Spielzeit",
+        Text::LongestPlay => "Längste Sitzung",
+        Text::LastPlayed => "Zuletzt gespielt",
+        Text::Minutes => "Min.",
+        Text::Achievements => "Abzeichen und Punkte",
+        Text::NoAchievements => "Noch nichts hier",
+        Text::Xp => "EP",
+        Text::Earned => "erhalten",
+        Text::Locked => "verborgen",
+        Text::NoScores => "noch keine Punkte",
+    }
+}
@@ -1,8 +1,8 @@
-use crate::UiPos;
+use crate::{play::UiPos, settings::KeyBindings};
 use firefly_hal::{InputState, Pad};
 use macroquad::prelude::*;
 
-pub fn input(ui: &UiPos) -> InputState {
+pub fn input(ui: &UiPos, keys: &KeyBindings) -> InputState {
     let mut pad = None;
 
     // soft pad on touch screen
@@ -19,19 +19,19 @@ pub fn input(ui: &UiPos) -> InputState {
         }
     }
 
-    if is_key_down(KeyCode::Left) || is_key_down(KeyCode::Key4) {
+    if is_any_key_down(&keys.left) {
         pad.get_or_insert_default().x = -1000;
     }
-    if is_key_down(KeyCode::Right) || is_key_down(KeyCode::Key6) {
+    if is_any_key_down(&keys.right) {
         pad.get_or_insert_default().x = 1000;
     }
-    if is_key_down(KeyCode::Up) || is_key_down(KeyCode::Key8) {
+    if is_any_key_down(&keys.up) {
         pad.get_or_insert_default().y = 1000;
     }
-    if is_key_down(KeyCode::Down) || is_key_down(KeyCode::Key2) {
+    if is_any_key_down(&keys.down) {
         pad.get_or_insert_default().y = -1000;
     }
-    if is_key_down(KeyCode::Key5) {
+    if is_any_key_down(&keys.center) {
         pad.get_or_insert_default();
     }
 
@@ -45,22 +45,150 @@ pub fn input(ui: &UiPos) -> InputState {
         }
     }
 
-    if is_key_down(KeyCode::Z) || is_key_down(KeyCode::Enter) || is_key_down(KeyCode::Space) {
+    if is_any_key_down(&keys.s) {
         buttons |= 1 << 0;
     }
-    if is_key_down(KeyCode::X) || is_key_down(KeyCode::B) || is_key_down(KeyCode::Backspace) {
+    if is_any_key_down(&keys.e) {
         buttons |= 1 << 1;
     }
-    if is_key_down(KeyCode::A) {
+    if is_any_key_down(&keys.w) {
         buttons |= 1 << 2;
     }
-    if is_key_down(KeyCode::Y) || is_key_down(KeyCode::S) {
+    if is_any_key_down(&keys.n) {
         buttons |= 1 << 3;
     }
 
-    if is_key_down(KeyCode::Back) || is_key_down(KeyCode::Tab) {
+    if is_any_key_down(&keys.menu) {
         // Menu key
         buttons |= 1 << 4;
     }
     InputState { pad, buttons }
 }
+
+/// Check if any of the given keys is held down.
+fn is_any_key_down(keys: &[String]) -> bool {
+    keys.iter().filter_map(|k| key_code(k)).any(is_key_down)
+}
+
+/// Check if any of the given keys was pressed in this frame.
+pub fn is_any_key_pressed(keys: &[String]) -> bool {
+    keys.iter().filter_map(|k| key_code(k)).any(is_key_pressed)
+}
+
+pub fn key_name(key: KeyCode) -> String {
+    format!("{key:?}")
+}
+
+macro_rules! key_codes {
+    ($($key:ident),* $(,)?) => {
+        /// Parse a key name as produced by [`key_name`].
+        pub fn key_code(name: &str) -> Option<KeyCode> {
+            match name {
+                $(stringify!($key) => Some(KeyCode::$key),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+key_codes!(
+    Space,
+    Apostrophe,
+    Comma,
+    Minus,
+    Period,
+    Slash,
+    Key0,
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    Semicolon,
+    Equal,
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    LeftBracket,
+    Backslash,
+    RightBracket,
+    GraveAccent,
+    Escape,
+    Enter,
+    Tab,
+    Backspace,
+    Insert,
+    Delete,
+    Right,
+    Left,
+    Down,
+    Up,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    Kp0,
+    Kp1,
+    Kp2,
+    Kp3,
+    Kp4,
+    Kp5,
+    Kp6,
+    Kp7,
+    Kp8,
+    Kp9,
+    KpDecimal,
+    KpDivide,
+    KpMultiply,
+    KpSubtract,
+    KpAdd,
+    KpEnter,
+    KpEqual,
+    LeftShift,
+    LeftControl,
+    LeftAlt,
+    RightShift,
+    RightControl,
+    RightAlt,
+    Menu,
+    Back,
+);
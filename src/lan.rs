@@ -0,0 +1,212 @@
+//! Finding other emulators on the local network for multiplayer.
+//!
+//! Connecting to peers and keeping frames in sync is done by the runtime
+//! (`NetHandler`) over the UDP network of the device, which only talks to
+//! the IP addresses it's configured with. This module fills that list:
+//! while the launcher runs, every instance announces itself by broadcast
+//! and remembers who answered.
+//!
+//! Devices listen on the address our own broadcasts come from, so that the ones
+//! on the same machine reach each other there and each can tell its own
+//! address apart from theirs.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Each instance listens on the first free port, so several can run on one machine.
+const PORTS: std::ops::Range<u16> = 3120..3128;
+const HELLO: &[u8] = b"fireflydroid-hello";
+const INTERVAL: Duration = Duration::from_secs(2);
+/// Forget instances that didn't announce themselves for this long.
+const TIMEOUT: Duration = Duration::from_secs(7);
+/// How long to wait for the running instances to answer a new one.
+const ANSWER_TIME: Duration = Duration::from_millis(300);
+
+struct Discovery {
+    /// Counts the starts, so that a thread left over from an earlier one stops
+    generation: u64,
+    running: bool,
+    /// Sent with every announcement to recognize our own when it comes back
+    nonce: u64,
+    /// Addresses our own announcements came from
+    local: Vec<IpAddr>,
+    /// Other instances and when they were last heard from
+    found: Vec<(SocketAddr, Instant)>,
+}
+
+static DISCOVERY: Mutex<Discovery> = Mutex::new(Discovery {
+    generation: 0,
+    running: false,
+    nonce: 0,
+    local: Vec::new(),
+    found: Vec::new(),
+});
+
+/// Announces this instance and listens for others in the background until dropped.
+pub struct Lan {
+    generation: u64,
+    /// The port discovery listens on
+    pub port: u16,
+}
+
+impl Drop for Lan {
+    fn drop(&mut self) {
+        let mut discovery = DISCOVERY.lock().unwrap();
+        if discovery.generation == self.generation {
+            discovery.running = false;
+            discovery.found.clear();
+        }
+    }
+}
+
+/// Start announcing this instance and listening for others.
+///
+/// Waits briefly for the instances that are already running to answer,
+/// so that a device created right after knows them.
+pub fn start() -> std::io::Result<Lan> {
+    let socket = bind()?;
+    let port = socket.local_addr()?.port();
+    let generation = {
+        let mut discovery = DISCOVERY.lock().unwrap();
+        discovery.generation += 1;
+        discovery.running = true;
+        discovery.nonce = RandomState::new().build_hasher().finish();
+        discovery.local.clear();
+        discovery.found.clear();
+        discovery.generation
+    };
+    std::thread::spawn(move || discover(&socket, generation));
+    std::thread::sleep(ANSWER_TIME);
+    Ok(Lan { generation, port })
+}
+
+/// The address for the device to listen on: the one on the local network if known.
+pub fn udp_ip() -> IpAddr {
+    let discovery = DISCOVERY.lock().unwrap();
+    let lan = discovery.local.iter().find(|ip| !ip.is_loopback());
+    match lan {
+        Some(ip) if discovery.running => *ip,
+        _ => Ipv4Addr::LOCALHOST.into(),
+    }
+}
+
+/// IP addresses of all emulators heard from recently.
+///
+/// Instances on this machine are reached on [`udp_ip`], which is listed
+/// only if there are any, so that a device alone doesn't find itself.
+///
+/// The device only takes the list when it's created, so call it again for every
+/// new device to pick up the peers that came and went since.
+pub fn peers() -> Vec<IpAddr> {
+    let own = udp_ip();
+    let discovery = DISCOVERY.lock().unwrap();
+    let mut peers = vec![];
+    for (addr, seen) in &discovery.found {
+        if seen.elapsed() >= TIMEOUT {
+            continue;
+        }
+        let ip = addr.ip();
+        let ip = if ip.is_loopback() || discovery.local.contains(&ip) {
+            own
+        } else {
+            ip
+        };
+        if !peers.contains(&ip) {
+            peers.push(ip);
+        }
+    }
+    peers
+}
+
+/// Where the other instances heard from recently sent their announcements from.
+pub fn instances() -> Vec<SocketAddr> {
+    let discovery = DISCOVERY.lock().unwrap();
+    discovery
+        .found
+        .iter()
+        .filter(|(_, seen)| seen.elapsed() < TIMEOUT)
+        .map(|(addr, _)| *addr)
+        .collect()
+}
+
+fn bind() -> std::io::Result<UdpSocket> {
+    let mut last_err = None;
+    for port in PORTS {
+        match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)) {
+            Ok(socket) => {
+                socket.set_broadcast(true)?;
+                return Ok(socket);
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap())
+}
+
+fn discover(socket: &UdpSocket, generation: u64) {
+    let nonce = DISCOVERY.lock().unwrap().nonce;
+    let hello = [HELLO, &nonce.to_le_bytes()].concat();
+    let mut buf = [0; 64];
+    loop {
+        {
+            let mut discovery = DISCOVERY.lock().unwrap();
+            if !discovery.running || discovery.generation != generation {
+                return;
+            }
+            discovery.found.retain(|(_, seen)| seen.elapsed() < TIMEOUT);
+        }
+        announce(socket, &hello);
+        // Listen until the next announcement is due.
+        let until = Instant::now() + INTERVAL;
+        loop {
+            let left = until.saturating_duration_since(Instant::now());
+            if left.is_zero() || socket.set_read_timeout(Some(left)).is_err() {
+                break;
+            }
+            let Ok((size, from)) = socket.recv_from(&mut buf) else {
+                continue;
+            };
+            let Some(sender) = buf[..size]
+                .strip_prefix(HELLO)
+                .and_then(|rest| <[u8; 8]>::try_from(rest).ok())
+                .map(u64::from_le_bytes)
+            else {
+                continue;
+            };
+            let mut discovery = DISCOVERY.lock().unwrap();
+            if discovery.generation != generation {
+                return;
+            }
+            if sender == nonce {
+                if !discovery.local.contains(&from.ip()) {
+                    discovery.local.push(from.ip());
+                }
+                continue;
+            }
+            let now = Instant::now();
+            match discovery.found.iter_mut().find(|(addr, _)| *addr == from) {
+                Some((_, seen)) => *seen = now,
+                None => {
+                    discovery.found.push((from, now));
+                    // Answer right away instead of on the next announcement,
+                    // the new instance might be about to create its device.
+                    _ = socket.send_to(&hello, from);
+                }
+            }
+        }
+    }
+}
+
+fn announce(socket: &UdpSocket, hello: &[u8]) {
+    for ip in [Ipv4Addr::BROADCAST, Ipv4Addr::LOCALHOST] {
+        for port in PORTS {
+            // Not every network allows broadcasts, loopback still works then.
+            _ = socket.send_to(hello, SocketAddr::from((ip, port)));
+        }
+    }
+}
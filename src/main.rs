@@ -1,188 +1,296 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 
-use firefly_hal::{DeviceConfig, DeviceImpl};
-use firefly_runtime::{FullID, NetHandler};
-use kaolin::{prelude::*, style::TextStyle};
+use firefly_runtime::FullID;
 use macroquad::prelude::*;
 
+mod achievements;
+mod audio;
+mod backup;
 mod catalog;
+mod cli;
+mod crash;
 mod drawing;
+mod frame_advance;
+mod headless;
+mod i18n;
 mod input;
+mod lan;
+mod nav;
+mod play;
+mod recording;
+mod replay;
+mod runtime_error;
+mod runtime_log;
+mod safe_area;
+mod screenshot;
+mod settings;
+mod stats;
+mod timestep;
 mod ui;
 
 struct HostState {
     screen: Image,
 }
 
-#[macroquad::main("fireflydroid")]
-async fn main() {
-    set_panic_handler(|msg, backtrace| async move {
-        let mut ui = ui::Renderer::new();
-
-        loop {
-            clear_background(RED);
-            ui.draw(|k| {
-                k.styled(
-                    FlexStyle::new()
-                        .background_color(GRAY.into())
-                        .layout(Layout::new().direction(Direction::TopToBottom))
-                        .sizing(sizing!(grow!())),
-                    |k| {
-                        let style = TextStyle::new().font_size(80.0).color(BLACK.into());
-                        k.text(&msg, style).text(&backtrace, style)
-                    },
-                )
-            });
-            next_frame().await;
+impl HostState {
+    fn new() -> Self {
+        HostState {
+            screen: Image {
+                width: 240,
+                height: 160,
+                bytes: vec![0; 240 * 160 * 4],
+            },
         }
-    });
-
-    catalog::list().await;
+    }
 }
 
-async fn play(id: &FullID) -> Result<(), firefly_runtime::Error> {
-    let project_path = dir();
-
-    let state = HostState {
-        screen: Image {
-            width: 240,
-            height: 160,
-            bytes: vec![0; 240 * 160 * 4],
-        },
-    };
-
-    let device = DeviceConfig {
-        root: project_path.to_owned(),
-        ..DeviceConfig::default()
-    };
+/// Directory with the ROMs, their data and the settings.
+///
+/// Can be overridden with `--root` or the `FIREFLYDROID_ROOT` environment variable,
+/// for example to share the apps with the desktop emulator.
+static ROOT: OnceLock<PathBuf> = OnceLock::new();
+const ROOT_VAR: &str = "FIREFLYDROID_ROOT";
 
-    let device = DeviceImpl::new(device);
-    let config = firefly_runtime::RuntimeConfig {
-        id: Some(id.clone()),
-        device,
-        display: state,
-        net_handler: NetHandler::None,
+fn main() {
+    let args = match cli::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
     };
-    let mut runtime = firefly_runtime::Runtime::new(config)?;
-    runtime.start()?;
-    while !is_key_pressed(KeyCode::Escape) {
-        clear_background(GRAY);
-
-        let exit = runtime.update()?;
-        // Exit requested. Finalize runtime and get ownership of the device back.
-        if exit {
-            let config = runtime.finalize()?;
-            runtime = firefly_runtime::Runtime::new(config)?;
-            runtime.start()?;
+    let root = args
+        .root
+        .or_else(|| std::env::var_os(ROOT_VAR).map(PathBuf::from));
+    match root {
+        Some(root) => ROOT.set(root).unwrap(),
+        None => migrate_from_cache(),
+    }
+    // The window shows it itself.
+    let windowed = matches!(args.command, cli::Command::Gui | cli::Command::Run(_));
+    if let (Some(msg), false) = (MIGRATION_ERROR.get(), windowed) {
+        eprintln!("{msg}");
+    }
+    let result = match args.command {
+        cli::Command::Gui => {
+            macroquad::Window::new("fireflydroid", gui(None));
+            Ok(())
         }
-
-        let ui = calc_ui_pos();
-        let input = input::input(&ui);
-        runtime.device_mut().update_input(input);
-
-        let screen = Texture2D::from_image(&runtime.display_mut().screen);
-        screen.set_filter(FilterMode::Nearest);
-        draw_texture_ex(
-            &screen,
-            ui.x,
-            0.,
-            WHITE,
-            DrawTextureParams {
-                dest_size: Some(ui.size),
-                source: None,
-                rotation: 0.,
-                flip_x: false,
-                flip_y: false,
-                pivot: None,
-            },
-        );
-
-        draw_circle_lines(ui.pad.p.x, ui.pad.p.y, ui.pad.r, 5., BLACK);
-
-        for (label, button) in ["S", "E", "W", "N"].iter().zip(&ui.buttons) {
-            draw_circle(button.p.x, button.p.y, button.r, BLACK);
-            let size = get_text_center(label, None, button.r as u16, 1.0, 0.);
-            draw_text(
-                label,
-                button.p.x - size.x,
-                button.p.y - size.y,
-                button.r,
-                WHITE,
-            );
+        cli::Command::Run(id) => {
+            macroquad::Window::new("fireflydroid", gui(Some(id)));
+            Ok(())
         }
+        cli::Command::Install(path) => cli::install(&path).map(|id| {
+            println!("installed {}.{}", id.author(), id.app());
+        }),
+        cli::Command::List => cli::list().map(|ids| {
+            for id in ids {
+                println!("{id}");
+            }
+        }),
+        cli::Command::Headless(args) => headless::run(&args),
+        cli::Command::Peers(secs) => cli::peers(secs).map(|(port, found, peers)| {
+            println!("listening on port {port}");
+            for addr in found {
+                println!("found {addr}");
+            }
+            for ip in peers {
+                println!("peer {ip}");
+            }
+        }),
+    };
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+// The window isn't opened by `#[macroquad::main]`, so the entry point
+// that the Android activity calls has to be exported by hand.
+#[cfg(target_os = "android")]
+#[no_mangle]
+pub extern "C" fn quad_main() {
+    main();
+}
 
-        next_frame().await;
+/// Show the catalog, or only run the given app (or the launcher) and close once it quits.
+async fn gui(app: Option<Option<FullID>>) {
+    #[cfg(unix)]
+    runtime_log::capture_output();
+    set_panic_handler(|msg, backtrace| async move {
+        crash::report(&msg, &backtrace).await;
+        show_catalog().await;
+    });
+    if let Some(msg) = MIGRATION_ERROR.get() {
+        crash::report(msg, "").await;
+    }
+    match app {
+        Some(id) => nav::run(nav::Screen::Play(id)).await,
+        None => show_catalog().await,
     }
-    Ok(())
 }
 
-struct UiPos {
-    x: f32,
-    size: Vec2,
-    pad: Button,
-    buttons: [Button; 4],
+async fn show_catalog() {
+    nav::run(nav::Screen::Catalog(catalog::ListScreen::new())).await;
 }
 
-#[derive(Default)]
-struct Button {
-    p: Vec2,
-    r: f32,
+fn dir() -> PathBuf {
+    ROOT.get_or_init(|| match default_dirs() {
+        Some((_, data)) => data,
+        // Without a home directory, keep everything next to where the emulator was started.
+        None => PathBuf::from("fireflydroid"),
+    })
+    .clone()
 }
 
-impl From<(Vec2, f32)> for Button {
-    fn from((p, r): (Vec2, f32)) -> Self {
-        Button { p, r }
-    }
+/// The directory the apps used to be stored in and the one they're stored in now.
+#[cfg(not(target_os = "android"))]
+fn default_dirs() -> Option<(PathBuf, PathBuf)> {
+    let dirs = directories::ProjectDirs::from("de", "oliobk", "fireflydroid")?;
+    Some((dirs.cache_dir().to_owned(), dirs.data_dir().to_owned()))
 }
 
-fn calc_ui_pos() -> UiPos {
-    let portrait = screen_width() < screen_height();
-
-    let (x, w, h) = if portrait {
-        let w = screen_width();
-        let h = w / 240. * 160.;
-        (0., w, h)
-    } else {
-        let h = screen_height();
-        let w = h / 160. * 240.;
-        let x = screen_width() / 2. - w / 2.;
-        (x, w, h)
+/// The directory the apps used to be stored in and the one they're stored in now.
+///
+/// The old root is the private directory of the app, the new one is `files` in it.
+#[cfg(target_os = "android")]
+fn default_dirs() -> Option<(PathBuf, PathBuf)> {
+    use macroquad::miniquad::{
+        call_object_method,
+        native::android::{attach_jni_env, ACTIVITY},
     };
-    let size = vec2(w, h);
-    let pad: Button = if portrait {
-        let x = screen_width() / 3.;
-        let y = screen_height() - x;
-        (vec2(x, y), x)
-    } else {
-        (vec2(x / 2., x), x / 2.)
-    }
-    .into();
-
-    let buttons = if portrait {
-        let center = vec2(pad.p.x * 2.2, pad.p.y - pad.p.x * 1.3);
-        let offset = pad.p.x / 2.;
-        let r = offset * 0.66;
-        [
-            Button::from((center + vec2(0., offset), r)),
-            Button::from((center + vec2(offset, 0.), r)),
-            Button::from((center - vec2(offset, 0.), r)),
-            Button::from((center - vec2(0., offset), r)),
-        ]
-    } else {
-        Default::default()
+
+    let files = unsafe {
+        let env = attach_jni_env();
+        let file = call_object_method!(env, ACTIVITY, "getFilesDir", "()Ljava/io/File;");
+        file_path(env, file)?
     };
-    UiPos {
-        x,
-        size,
-        pad,
-        buttons,
+    Some((files.parent()?.to_owned(), files))
+}
+
+/// The absolute path of a `java.io.File`.
+#[cfg(target_os = "android")]
+unsafe fn file_path(
+    env: *mut macroquad::miniquad::native::android::ndk_sys::JNIEnv,
+    file: macroquad::miniquad::native::android::ndk_sys::jobject,
+) -> Option<PathBuf> {
+    use macroquad::miniquad::call_object_method;
+
+    if file.is_null() {
+        return None;
     }
+    let path = call_object_method!(env, file, "getAbsolutePath", "()Ljava/lang/String;");
+    if path.is_null() {
+        return None;
+    }
+    let chars = ((**env).GetStringUTFChars.unwrap())(env, path, std::ptr::null_mut());
+    let result = std::ffi::CStr::from_ptr(chars)
+        .to_string_lossy()
+        .into_owned();
+    ((**env).ReleaseStringUTFChars.unwrap())(env, path, chars);
+    Some(PathBuf::from(result))
 }
 
-fn dir() -> PathBuf {
-    let Some(dirs) = directories::ProjectDirs::from("de", "oliobk", "fireflydroid") else {
-        return PathBuf::from("/data/data/de.oliobk.fireflydroid");
+/// Everything in the root that is moved to the new location.
+///
+/// The ROMs and the app data come last: once they're in place,
+/// the migration counts as done.
+const ROOT_ENTRIES: &[&str] = &[
+    "settings.json",
+    "sys",
+    "states",
+    "screenshots",
+    "recordings",
+    "replays",
+    "backups",
+    "crash_reports",
+    "roms",
+    "data",
+];
+
+/// Set if moving the apps to the new location failed, reported once the window is open.
+static MIGRATION_ERROR: OnceLock<String> = OnceLock::new();
+
+/// Move the apps and their data out of the old location, where the system
+/// may clear them at any time.
+fn migrate_from_cache() {
+    let Some((old, new)) = default_dirs() else {
+        return;
     };
+    let has_apps = |dir: &Path| dir.join("roms").exists() || dir.join("data").exists();
+    if !has_apps(&old) || has_apps(&new) {
+        return;
+    }
+    if let Err(e) = move_root(&old, &new) {
+        let msg = format!(
+            "failed to move the apps from {} to {}: {e}",
+            old.display(),
+            new.display()
+        );
+        runtime_log::record(msg.clone());
+        _ = MIGRATION_ERROR.set(msg);
+    }
+}
+
+/// Copy everything to a sibling of `new` first, so that a failure halfway
+/// leaves the old location untouched and the next start tries again.
+fn move_root(old: &Path, new: &Path) -> std::io::Result<()> {
+    let partial = new.with_extension("partial");
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)?;
+    }
+    std::fs::create_dir_all(&partial)?;
+    for name in ROOT_ENTRIES {
+        let from = old.join(name);
+        if from.is_dir() {
+            copy_dir(&from, &partial.join(name))?;
+        } else if from.exists() {
+            std::fs::copy(&from, partial.join(name))?;
+        }
+    }
+    // Renames within the same directory are quick and don't fail halfway.
+    std::fs::create_dir_all(new)?;
+    for name in ROOT_ENTRIES {
+        let (from, to) = (partial.join(name), new.join(name));
+        if !from.exists() {
+            continue;
+        }
+        // Without apps in the new location, whatever is there is less valuable than the old one.
+        if to.is_dir() {
+            std::fs::remove_dir_all(&to)?;
+        }
+        std::fs::rename(from, to)?;
+    }
+    _ = std::fs::remove_dir_all(&partial);
+    for name in ROOT_ENTRIES {
+        let path = old.join(name);
+        _ = if path.is_dir() {
+            std::fs::remove_dir_all(path)
+        } else {
+            std::fs::remove_file(path)
+        };
+    }
+    Ok(())
+}
 
-    dirs.cache_dir().to_owned()
+/// Copy a directory recursively, creating `to` even if `from` doesn't exist.
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    // The app might not have written any data yet.
+    if !from.exists() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,84 @@
+//! Navigation between screens.
+
+use firefly_runtime::FullID;
+use macroquad::prelude::*;
+
+use crate::{
+    achievements::AchievementsScreen,
+    backup::BackupScreen,
+    catalog::{AppScreen, ListScreen},
+    runtime_error::ErrorScreen,
+    settings::SettingsScreen,
+};
+
+pub enum Screen {
+    Catalog(ListScreen),
+    App(AppScreen),
+    Settings(SettingsScreen),
+    /// Run the app, or the launcher of the device if there is no ID
+    Play(Option<FullID>),
+    Error(ErrorScreen),
+    Backup(BackupScreen),
+    Achievements(AchievementsScreen),
+}
+
+pub enum Nav {
+    /// Open a new screen on top of the current one
+    Push(Screen),
+    /// Go back to the previous screen
+    Pop,
+    /// Swap the current screen for a new one
+    Replace(Screen),
+    /// Close everything but the first screen, or the first screen itself if it's the only one
+    Home,
+}
+
+impl Screen {
+    /// Run the screen until it wants to navigate somewhere else.
+    ///
+    /// `only` tells if there is no other screen below this one.
+    async fn show(&mut self, only: bool) -> Nav {
+        match self {
+            Screen::Catalog(list) => list.show().await,
+            Screen::App(app) => app.show().await,
+            Screen::Settings(settings) => settings.show().await,
+            Screen::Play(id) => match crate::play::play(id.as_ref(), only).await {
+                Ok(()) => Nav::Home,
+                Err(e) => Nav::Replace(Screen::Error(ErrorScreen::new(id.clone(), e))),
+            },
+            Screen::Error(error) => error.show().await,
+            Screen::Backup(backup) => backup.show().await,
+            Screen::Achievements(achievements) => achievements.show().await,
+        }
+    }
+}
+
+/// Show screens until the last one is closed.
+///
+/// Screens that are covered by another one keep their state,
+/// including the scroll position, until they are shown again.
+pub async fn run(root: Screen) {
+    let mut stack = vec![root];
+    while !stack.is_empty() {
+        let only = stack.len() == 1;
+        let nav = stack.last_mut().unwrap().show(only).await;
+        // Don't let the click or key press that caused the navigation
+        // leak into the next screen.
+        next_frame().await;
+        match nav {
+            Nav::Push(screen) => stack.push(screen),
+            Nav::Pop => {
+                stack.pop();
+            }
+            Nav::Replace(screen) => *stack.last_mut().unwrap() = screen,
+            // When started with an app instead of the catalog, quitting it closes the window.
+            Nav::Home if stack.len() == 1 => stack.clear(),
+            Nav::Home => stack.truncate(1),
+        }
+    }
+}
+
+/// Check if the user wants to go back: Back on Android, Escape on desktop.
+pub fn back_pressed() -> bool {
+    is_key_pressed(KeyCode::Back) || is_key_pressed(KeyCode::Escape)
+}
@@ -0,0 +1,670 @@
+use std::path::PathBuf;
+
+use firefly_hal::{Device, DeviceConfig, DeviceImpl};
+use firefly_runtime::{FullID, NetHandler};
+use kaolin::prelude::*;
+use macroquad::prelude::*;
+
+use crate::{
+    audio::AudioOutput,
+    dir,
+    frame_advance::FrameAdvance,
+    i18n::Text,
+    input, lan, nav,
+    recording::{Recording, Saving},
+    replay::{InputRecorder, Replay, Sandbox},
+    runtime_log, safe_area, screenshot,
+    settings::{Scaling, Settings, SettingsScreen, TouchLayout},
+    stats::Session,
+    timestep::{FixedStep, Speed},
+    ui::{self, BUTTON_FONT_SIZE, TITLE_FONT_SIZE},
+    HostState,
+};
+
+enum MenuAction {
+    Resume,
+    Restart,
+    Screenshot,
+    Record,
+    RecordInput,
+    Replay,
+    Settings,
+    Quit,
+}
+
+/// Run the app until the user quits or it exits.
+///
+/// Without an ID, the launcher of the device is started instead, and apps it starts
+/// return to it when they exit. If the app is `standalone`, there is no catalog
+/// to go back to and quitting closes the window.
+pub async fn play(id: Option<&FullID>, standalone: bool) -> Result<(), firefly_runtime::Error> {
+    let project_path = dir();
+    let mut settings = Settings::load();
+
+    let launcher = id.is_none();
+    runtime_log::clear();
+    runtime_log::record(match id {
+        Some(id) => format!("starting {}.{}", id.author(), id.app()),
+        None => "starting the launcher".to_owned(),
+    });
+    // Multiplayer is started from the launcher, so only look for other players while it runs.
+    let _lan = if launcher {
+        lan::start()
+            .map_err(|e| runtime_log::record(format!("LAN discovery disabled: {e}")))
+            .ok()
+    } else {
+        None
+    };
+    let new_device = |root: PathBuf| {
+        DeviceImpl::new(DeviceConfig {
+            root,
+            // Reachable from the local network, not just from this machine.
+            udp_ip: lan::udp_ip(),
+            peers: lan::peers(),
+            ..DeviceConfig::default()
+        })
+    };
+
+    let device = new_device(project_path.to_owned());
+    // Only read once, it's needed every frame.
+    let launcher_id = launcher_id();
+    // The app that is running, or `None` for the launcher.
+    let mut current = id.cloned();
+    let config = firefly_runtime::RuntimeConfig {
+        id: current.clone(),
+        device,
+        display: HostState::new(),
+        // The launcher asks the runtime to connect to the peers, which then replaces
+        // this with its own handler and hands the connection on to the app it starts.
+        net_handler: NetHandler::None,
+    };
+    // Counts the play time when dropped, including on quitting and on errors.
+    let mut session = current.as_ref().map(|id| Session::start(id, 1));
+    let mut runtime = firefly_runtime::Runtime::new(config)?;
+    runtime.start()?;
+    let audio = AudioOutput::new(settings.volume, settings.audio_latency);
+    let mut clock = FixedStep::new();
+    let mut menu: Option<ui::Renderer> = None;
+    let mut muted = false;
+    let mut frame_advance = FrameAdvance::default();
+    let mut toast: Option<ui::Toast> = None;
+    let mut recording: Option<Recording> = None;
+    let mut saving: Option<Saving> = None;
+    let mut input_recorder: Option<InputRecorder> = None;
+    let mut replay: Option<Replay> = None;
+    // Where the app runs while replaying, so that the real data stays as it is.
+    let mut sandbox: Option<Sandbox> = None;
+    loop {
+        clear_background(settings.theme.background());
+        let ui = calc_ui_pos(&settings);
+        let app = current.clone().unwrap_or_else(|| launcher_id.clone());
+        let id = &app;
+
+        let paused = menu.is_some();
+        let mut restart = false;
+        // The app exited, restarted, or (in the launcher) started another app.
+        let mut switch = false;
+        // Recording and replaying input both start from a fresh start of the app.
+        let mut start_input_recording = false;
+        let mut start_replay = false;
+        if let Some(renderer) = &mut menu {
+            draw_game(&runtime.display_mut().screen, &ui, &settings);
+            let action = if nav::back_pressed() {
+                Some(MenuAction::Resume)
+            } else {
+                pause_menu(
+                    renderer,
+                    &settings,
+                    recording.is_some(),
+                    input_recorder.is_some(),
+                    if standalone {
+                        Text::Quit
+                    } else {
+                        Text::QuitToCatalog
+                    },
+                )
+            };
+            match action {
+                Some(MenuAction::Resume) => menu = None,
+                Some(MenuAction::Restart) => {
+                    restart = true;
+                    menu = None;
+                }
+                Some(MenuAction::Screenshot) => {
+                    toast = Some(take_screenshot(
+                        id,
+                        &runtime.display_mut().screen,
+                        &settings,
+                    ));
+                }
+                Some(MenuAction::Record) => {
+                    toast = toggle_recording(&mut recording, &mut saving, id, &settings);
+                }
+                Some(MenuAction::RecordInput) => match input_recorder.take() {
+                    Some(recorder) => {
+                        toast = Some(finish_input_recording(recorder, id, &settings));
+                    }
+                    None => {
+                        start_input_recording = true;
+                        menu = None;
+                    }
+                },
+                Some(MenuAction::Replay) => {
+                    start_replay = true;
+                    menu = None;
+                }
+                Some(MenuAction::Settings) => {
+                    // clear clicks
+                    next_frame().await;
+                    // Its own stack, so that the backup screen opened from it comes back to it.
+                    // The app stays loaded underneath and might write over data imported
+                    // for it, so the backup screen doesn't import that.
+                    // Boxed because the stack can run apps, which makes the future recursive.
+                    let screen = SettingsScreen::paused(id);
+                    Box::pin(nav::run(nav::Screen::Settings(screen))).await;
+                    settings = Settings::load();
+                    if let Some(audio) = &audio {
+                        audio.set_volume(settings.volume);
+                    }
+                }
+                Some(MenuAction::Quit) => {
+                    if let Some(recorder) = input_recorder.take() {
+                        finish_input_recording(recorder, id, &settings);
+                    }
+                    return Ok(());
+                }
+                None => {}
+            }
+        } else if pause_pressed(&ui, &settings) {
+            menu = Some(ui::Renderer::new(safe_area::insets(&settings)));
+            draw_game(&runtime.display_mut().screen, &ui, &settings);
+        }
+        if paused && menu.is_none() {
+            // Don't catch up on the time spent in the menu.
+            clock.reset();
+        }
+
+        if menu.is_none() {
+            if speed_pressed(&ui, &settings) {
+                clock.speed = clock.speed.next();
+            }
+            if input::is_any_key_pressed(&settings.keys.screenshot) {
+                toast = Some(take_screenshot(
+                    id,
+                    &runtime.display_mut().screen,
+                    &settings,
+                ));
+            }
+            if input::is_any_key_pressed(&settings.keys.record) {
+                toast = toggle_recording(&mut recording, &mut saving, id, &settings);
+            }
+            if frame_advance.toggle(&settings.keys) {
+                clock.reset();
+            }
+            let mut input = input::input(&ui, &settings.keys);
+            let updates = if frame_advance.active {
+                input = frame_advance.input(&settings.keys, input);
+                frame_advance.updates(&settings.keys)
+            } else {
+                clock.updates()
+            };
+
+            for _ in 0..updates {
+                let input = match replay.as_mut().map(Replay::next) {
+                    Some(Some(recorded)) => recorded,
+                    Some(None) => {
+                        replay = None;
+                        toast = Some(ui::Toast::new(settings.tr(Text::ReplayOver).to_owned()));
+                        input.clone()
+                    }
+                    None => input.clone(),
+                };
+                if let Some(recorder) = &mut input_recorder {
+                    recorder.record(&input);
+                }
+                runtime.device_mut().update_input(input);
+                let exit = runtime.update()?;
+                if let Some(clip) = &mut recording {
+                    clip.record(&runtime.display_mut().screen);
+                }
+                // The runtime mixes the audio of each update into the device buffer.
+                if let Some(audio) = &audio {
+                    audio.push(runtime.device_mut().get_audio_buffer());
+                }
+                if exit {
+                    switch = true;
+                    break;
+                }
+            }
+            // Exit requested. Finalize runtime and get ownership of the device back.
+            if restart || switch || start_input_recording || start_replay {
+                let mut config = runtime.finalize()?;
+                if switch {
+                    // The runtime sets the app to start next: the same one when it
+                    // restarts itself, another one when the launcher starts it,
+                    // and none when it exits.
+                    let restarted = config.id.is_some() && config.id == current;
+                    if !launcher && !restarted {
+                        runtime_log::record("exit requested, back to the catalog");
+                        if let Some(recorder) = input_recorder.take() {
+                            finish_input_recording(recorder, id, &settings);
+                        }
+                        return Ok(());
+                    }
+                    runtime_log::record(match &config.id {
+                        _ if restarted => "restart requested".to_owned(),
+                        Some(next) => format!("starting {}.{}", next.author(), next.app()),
+                        None => "exit requested, back to the launcher".to_owned(),
+                    });
+                    current = config.id.clone();
+                } else {
+                    config.id = current.clone();
+                }
+                // The recording covers a single run of the app, which ends here.
+                if let Some(recorder) = input_recorder.take() {
+                    toast = Some(finish_input_recording(recorder, id, &settings));
+                }
+                if start_input_recording {
+                    match InputRecorder::start(id) {
+                        Ok(recorder) => input_recorder = Some(recorder),
+                        Err(e) => runtime_log::record(format!("failed to record input: {e}")),
+                    }
+                }
+                // Restarting in any way ends a replay.
+                replay = None;
+                let was_sandboxed = sandbox.take().is_some();
+                if start_replay {
+                    match Replay::load_latest(id) {
+                        Ok((loaded, root)) => {
+                            replay = Some(loaded);
+                            sandbox = Some(root);
+                        }
+                        Err(e) => toast = Some(ui::Toast::new(e.to_string())),
+                    }
+                }
+                // A multiplayer connection goes on over the network of the old device.
+                // Otherwise a new device picks up the peers that came and went since.
+                let connected = !matches!(config.net_handler, NetHandler::None);
+                if !connected || was_sandboxed || sandbox.is_some() {
+                    config.device =
+                        new_device(sandbox.as_ref().map_or_else(dir, |s| s.root().to_owned()));
+                }
+                // Restarting the same app continues its session, switching starts a new one.
+                if session.as_ref().map(Session::id) != current.as_ref() {
+                    drop(session.take());
+                    let players = players(&config.net_handler);
+                    session = current.as_ref().map(|id| Session::start(id, players));
+                }
+                runtime = firefly_runtime::Runtime::new(config)?;
+                runtime.start()?;
+            }
+
+            draw_game(&runtime.display_mut().screen, &ui, &settings);
+            draw_speed(&ui, clock.speed);
+            if recording.as_ref().is_some_and(Recording::is_full) {
+                toast = toggle_recording(&mut recording, &mut saving, id, &settings);
+            }
+            if recording.is_some() {
+                let r = ui.pause.h / 4.;
+                draw_circle(ui.x + r * 2., ui.y + r * 2., r, RED);
+            }
+            if frame_advance.active {
+                frame_advance.draw(&ui, &input, &settings);
+            }
+        }
+
+        if let Some(result) = saving.as_ref().and_then(Saving::poll) {
+            saving = None;
+            toast = Some(match result {
+                Ok(()) => ui::Toast::new(settings.tr(Text::RecordingSaved).to_owned()),
+                Err(e) => {
+                    runtime_log::record(format!("failed to save recording: {e}"));
+                    ui::Toast::new(e.to_string())
+                }
+            });
+        }
+        if toast.as_ref().is_some_and(|t| !t.draw()) {
+            toast = None;
+        }
+
+        // Watching a replay isn't playing.
+        let playing = menu.is_none() && !frame_advance.active && replay.is_none();
+        if let Some(session) = &mut session {
+            session.set_playing(playing);
+        }
+
+        // Pitch correction is out of scope, so the app is silent unless it runs at normal speed.
+        let mute = menu.is_some() || frame_advance.active || clock.speed != Speed::Normal;
+        if mute != muted {
+            muted = mute;
+            if let Some(audio) = &audio {
+                audio.set_muted(muted);
+            }
+        }
+
+        next_frame().await;
+    }
+}
+
+fn draw_game(screen: &Image, ui: &UiPos, settings: &Settings) {
+    let screen = Texture2D::from_image(screen);
+    screen.set_filter(FilterMode::Nearest);
+    draw_texture_ex(
+        &screen,
+        ui.x,
+        ui.y,
+        WHITE,
+        DrawTextureParams {
+            dest_size: Some(ui.size),
+            source: None,
+            rotation: 0.,
+            flip_x: false,
+            flip_y: false,
+            pivot: None,
+        },
+    );
+
+    let foreground = settings.theme.foreground();
+    draw_circle_lines(ui.pad.p.x, ui.pad.p.y, ui.pad.r, 5., foreground);
+
+    for (label, button) in ["S", "E", "W", "N"].iter().zip(&ui.buttons) {
+        draw_circle(button.p.x, button.p.y, button.r, foreground);
+        let size = get_text_center(label, None, button.r as u16, 1.0, 0.);
+        draw_text(
+            label,
+            button.p.x - size.x,
+            button.p.y - size.y,
+            button.r,
+            settings.theme.background(),
+        );
+    }
+
+    // Pause icon: two vertical bars
+    let Rect { x, y, w, h } = ui.pause;
+    let color = Color::new(1., 1., 1., 0.5);
+    draw_rectangle(x + w * 0.2, y + h * 0.2, w * 0.2, h * 0.6, color);
+    draw_rectangle(x + w * 0.6, y + h * 0.2, w * 0.2, h * 0.6, color);
+}
+
+/// Check if the pause key was pressed or the pause icon tapped.
+fn pause_pressed(ui: &UiPos, settings: &Settings) -> bool {
+    let tapped = touches()
+        .iter()
+        .any(|t| matches!(t.phase, TouchPhase::Started) && ui.pause.contains(t.position));
+    let clicked =
+        is_mouse_button_pressed(MouseButton::Left) && ui.pause.contains(mouse_position().into());
+    tapped
+        || clicked
+        || settings
+            .keys
+            .pause
+            .iter()
+            .filter_map(|k| input::key_code(k))
+            .any(is_key_pressed)
+}
+
+/// ID of the launcher set in the device settings, for naming the directories
+/// of screenshots and recordings made while it runs.
+/// How many devices play the app that's handed the connection, this one included.
+fn players(net_handler: &NetHandler) -> usize {
+    match net_handler {
+        // The synced peers include this device.
+        NetHandler::FrameSyncer(syncer) => syncer.peers.len(),
+        _ => 1,
+    }
+}
+
+fn launcher_id() -> FullID {
+    std::fs::read_to_string(dir().join("sys").join("launcher"))
+        .ok()
+        .and_then(|raw| FullID::try_from(raw.trim()).ok())
+        .unwrap_or_else(|| FullID::try_from("sys.launcher").unwrap())
+}
+
+fn take_screenshot(id: &FullID, screen: &Image, settings: &Settings) -> ui::Toast {
+    match screenshot::save(id, screen, settings.screenshot_scale) {
+        Ok(_) => ui::Toast::new(settings.tr(Text::ScreenshotSaved).to_owned()),
+        Err(e) => ui::Toast::new(e.to_string()),
+    }
+}
+
+/// Start a new recording, or stop and save the current one.
+///
+/// The clip is encoded in the background, see `saving`.
+fn toggle_recording(
+    recording: &mut Option<Recording>,
+    saving: &mut Option<Saving>,
+    id: &FullID,
+    settings: &Settings,
+) -> Option<ui::Toast> {
+    let Some(clip) = recording.take() else {
+        *recording = Some(Recording::new());
+        return None;
+    };
+    if clip.is_empty() {
+        return Some(ui::Toast::new(settings.tr(Text::RecordingEmpty).to_owned()));
+    }
+    Some(match clip.save(id) {
+        Ok(pending) => {
+            *saving = Some(pending);
+            ui::Toast::new(settings.tr(Text::SavingRecording).to_owned())
+        }
+        Err(e) => ui::Toast::new(e.to_string()),
+    })
+}
+
+/// Save the input recorded so far as a replay.
+fn finish_input_recording(recorder: InputRecorder, id: &FullID, settings: &Settings) -> ui::Toast {
+    match recorder.finish(id) {
+        Ok(_) => ui::Toast::new(settings.tr(Text::InputSaved).to_owned()),
+        Err(e) => {
+            runtime_log::record(format!("failed to save input: {e}"));
+            ui::Toast::new(e.to_string())
+        }
+    }
+}
+
+fn draw_speed(ui: &UiPos, speed: Speed) {
+    let Rect { x, y, w, h } = ui.speed;
+    let color = match speed {
+        Speed::Normal => Color::new(1., 1., 1., 0.5),
+        _ => YELLOW,
+    };
+    let label = speed.label();
+    let size = measure_text(label, None, h as u16 / 2, 1.);
+    draw_text(
+        label,
+        x + w / 2. - size.width / 2.,
+        y + h / 2. + size.offset_y / 2.,
+        h / 2.,
+        color,
+    );
+}
+
+/// Check if the speed key was pressed or the speed indicator tapped.
+fn speed_pressed(ui: &UiPos, settings: &Settings) -> bool {
+    let tapped = touches()
+        .iter()
+        .any(|t| matches!(t.phase, TouchPhase::Started) && ui.speed.contains(t.position));
+    let clicked =
+        is_mouse_button_pressed(MouseButton::Left) && ui.speed.contains(mouse_position().into());
+    tapped
+        || clicked
+        || settings
+            .keys
+            .speed
+            .iter()
+            .filter_map(|k| input::key_code(k))
+            .any(is_key_pressed)
+}
+
+/// Draw the pause menu on top of the game and return the selected action.
+fn pause_menu(
+    renderer: &mut ui::Renderer,
+    settings: &Settings,
+    recording: bool,
+    recording_input: bool,
+    quit: Text,
+) -> Option<MenuAction> {
+    let theme = settings.theme;
+    renderer.insets = safe_area::insets(settings);
+    renderer.draw(|k| {
+        let style = TextStyle::new()
+            .font_size(BUTTON_FONT_SIZE)
+            .color(theme.foreground().into());
+        k.styled(
+            FlexStyle::new()
+                .background_color(Color::new(0., 0., 0., 0.6).into())
+                .layout(
+                    Layout::new()
+                        .direction(Direction::TopToBottom)
+                        .alignment(Alignment::Center)
+                        .justification(Justification::Center)
+                        .gap(20.),
+                )
+                .sizing(sizing!(grow!())),
+            |mut k| {
+                k = k.text(
+                    settings.tr(Text::Paused),
+                    style.font_size(TITLE_FONT_SIZE).color(WHITE.into()),
+                );
+                for (id, text) in [
+                    ("resume", Text::Resume),
+                    ("restart", Text::Restart),
+                    ("screenshot", Text::Screenshot),
+                    (
+                        "record",
+                        if recording {
+                            Text::StopRecording
+                        } else {
+                            Text::StartRecording
+                        },
+                    ),
+                    (
+                        "record_input",
+                        if recording_input {
+                            Text::StopRecordingInput
+                        } else {
+                            Text::RecordInput
+                        },
+                    ),
+                    ("replay", Text::ReplayInput),
+                    ("settings", Text::Settings),
+                    ("quit", quit),
+                ] {
+                    k = k.styled(
+                        FlexStyle::new()
+                            .custom(id)
+                            .background_color(theme.header().into())
+                            .border(Border {
+                                width: 3.,
+                                color: theme.border().into(),
+                            })
+                            .layout(Layout::new().justification(Justification::Center))
+                            .sizing(sizing!(grow!(), fit!())),
+                        |k| k.text(settings.tr(text), style),
+                    );
+                }
+                k
+            },
+        )
+    });
+    match renderer.clicked.iter().next().map(|s| s.as_str()) {
+        Some("resume") => Some(MenuAction::Resume),
+        Some("restart") => Some(MenuAction::Restart),
+        Some("screenshot") => Some(MenuAction::Screenshot),
+        Some("record") => Some(MenuAction::Record),
+        Some("record_input") => Some(MenuAction::RecordInput),
+        Some("replay") => Some(MenuAction::Replay),
+        Some("settings") => Some(MenuAction::Settings),
+        Some("quit") => Some(MenuAction::Quit),
+        Some(other) => panic!("{other}"),
+        None => None,
+    }
+}
+
+pub struct UiPos {
+    pub x: f32,
+    pub y: f32,
+    pub size: Vec2,
+    pub pad: Button,
+    pub buttons: [Button; 4],
+    /// Opens the pause menu when tapped
+    pub pause: Rect,
+    /// Shows the speed and cycles through the speeds when tapped
+    pub speed: Rect,
+}
+
+#[derive(Default)]
+pub struct Button {
+    pub p: Vec2,
+    pub r: f32,
+}
+
+impl From<(Vec2, f32)> for Button {
+    fn from((p, r): (Vec2, f32)) -> Self {
+        Button { p, r }
+    }
+}
+
+fn calc_ui_pos(settings: &Settings) -> UiPos {
+    let area = safe_area::rect(settings);
+    let portrait = area.w < area.h;
+
+    let mut scale = if portrait {
+        area.w / 240.
+    } else {
+        area.h / 160.
+    };
+    if settings.scaling == Scaling::Integer {
+        scale = scale.floor().max(1.);
+    }
+    let size = vec2(240., 160.) * scale;
+    let x = area.x + area.w / 2. - size.x / 2.;
+    let y = if portrait {
+        area.y
+    } else {
+        area.y + area.h / 2. - size.y / 2.
+    };
+
+    let pad: Button = if portrait {
+        let r = area.w / 3.;
+        (vec2(area.x + r, area.bottom() - r), r)
+    } else {
+        let margin = x - area.x;
+        (vec2(area.x + margin / 2., area.y + margin), margin / 2.)
+    }
+    .into();
+
+    let buttons = if portrait {
+        let center = vec2(area.x + pad.r * 2.2, pad.p.y - pad.r * 1.3);
+        let offset = pad.r / 2.;
+        let r = offset * 0.66;
+        [
+            Button::from((center + vec2(0., offset), r)),
+            Button::from((center + vec2(offset, 0.), r)),
+            Button::from((center - vec2(offset, 0.), r)),
+            Button::from((center - vec2(0., offset), r)),
+        ]
+    } else {
+        Default::default()
+    };
+    let pause_size = size.y / 8.;
+    let mut ui = UiPos {
+        x,
+        y,
+        size,
+        pad,
+        buttons,
+        pause: Rect::new(x + size.x - pause_size, y, pause_size, pause_size),
+        speed: Rect::new(x + size.x - pause_size * 3., y, pause_size * 2., pause_size),
+    };
+    if settings.touch_layout == TouchLayout::PadRight {
+        for button in std::iter::once(&mut ui.pad).chain(&mut ui.buttons) {
+            button.p.x = area.left() + area.right() - button.p.x;
+        }
+        // Keep the "E" button on the right of the "W" button.
+        ui.buttons.swap(1, 2);
+    }
+    ui
+}
@@ -0,0 +1,126 @@
+//! Recording the screen of the running app into an animated PNG.
+//!
+//! APNG has no audio track, so clips are silent.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+    sync::mpsc::{self, Receiver},
+};
+
+use firefly_runtime::FullID;
+use macroquad::prelude::*;
+
+use crate::timestep::UPDATES_PER_SECOND;
+
+/// Keep every this many updates, giving 30 frames per second.
+const FRAME_INTERVAL: u32 = 2;
+/// Recording stops on its own after this many seconds to keep memory in check.
+const MAX_SECONDS: f64 = 60.;
+const MAX_FRAMES: usize = (MAX_SECONDS * UPDATES_PER_SECOND) as usize / FRAME_INTERVAL as usize;
+
+pub struct Recording {
+    width: u16,
+    height: u16,
+    /// zstd compressed frames
+    frames: Vec<Vec<u8>>,
+    /// Updates since the last frame was kept
+    since_frame: u32,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Recording {
+            width: 0,
+            height: 0,
+            frames: vec![],
+            since_frame: FRAME_INTERVAL,
+        }
+    }
+
+    /// Call after every runtime update.
+    pub fn record(&mut self, screen: &Image) {
+        self.since_frame += 1;
+        if self.since_frame < FRAME_INTERVAL || self.is_full() {
+            return;
+        }
+        self.since_frame = 0;
+        self.width = screen.width;
+        self.height = screen.height;
+        if let Ok(frame) = zstd::bulk::compress(&screen.bytes, 1) {
+            self.frames.push(frame);
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.frames.len() >= MAX_FRAMES
+    }
+
+    /// No frame was kept yet, for example when stopped right away or while paused.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Encode the clip in the background.
+    pub fn save(self, id: &FullID) -> io::Result<Saving> {
+        let dir = crate::dir()
+            .join("recordings")
+            .join(id.author())
+            .join(id.app());
+        std::fs::create_dir_all(&dir)?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = dir.join(format!("{now}.png"));
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = self.encode(&path);
+            if result.is_err() {
+                // Don't leave a broken file behind.
+                _ = std::fs::remove_file(&path);
+            }
+            _ = sender.send(result);
+        });
+        Ok(Saving { result: receiver })
+    }
+
+    fn encode(&self, path: &Path) -> io::Result<()> {
+        let size = usize::from(self.width) * usize::from(self.height) * 4;
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width.into(), self.height.into());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(self.frames.len() as u32, 0)
+            .map_err(io::Error::other)?;
+        encoder
+            .set_frame_delay(FRAME_INTERVAL as u16, UPDATES_PER_SECOND as u16)
+            .map_err(io::Error::other)?;
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        for frame in &self.frames {
+            let frame = zstd::bulk::decompress(frame, size)?;
+            writer.write_image_data(&frame).map_err(io::Error::other)?;
+        }
+        writer.finish().map_err(io::Error::other)
+    }
+}
+
+/// A clip being encoded in the background.
+pub struct Saving {
+    result: Receiver<io::Result<()>>,
+}
+
+impl Saving {
+    /// The outcome of encoding, once it's done.
+    pub fn poll(&self) -> Option<io::Result<()>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Some(Err(io::Error::other("encoding the recording failed")))
+            }
+        }
+    }
+}
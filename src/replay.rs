@@ -0,0 +1,272 @@
+//! Recording the input of every update and feeding it back later.
+//!
+//! A replay starts from a fresh start of the app with the data it had
+//! when the recording began. It runs in a [`Sandbox`], so the real data
+//! of the app stays untouched.
+//!
+//! The device doesn't let the emulator seed its random number generator
+//! or fix its clock, so apps that use either can take a different course
+//! when replayed. Only apps that depend on nothing but their input and data
+//! replay exactly.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use firefly_hal::{InputState, Pad};
+use firefly_runtime::FullID;
+use miniserde::{Deserialize, Serialize};
+
+use crate::{copy_dir, dir};
+
+/// The directory where the app keeps its data.
+pub fn data_dir(id: &FullID) -> PathBuf {
+    dir().join("data").join(id.author()).join(id.app())
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReplayFile {
+    author: String,
+    app: String,
+    inputs: Vec<Input>,
+}
+
+/// The same input for a number of consecutive updates.
+#[derive(Serialize, Deserialize, PartialEq)]
+struct Input {
+    repeat: u32,
+    pad_x: Option<i16>,
+    pad_y: Option<i16>,
+    buttons: u8,
+}
+
+impl Input {
+    fn new(input: &InputState) -> Self {
+        Input {
+            repeat: 1,
+            pad_x: input.pad.as_ref().map(|p| p.x),
+            pad_y: input.pad.as_ref().map(|p| p.y),
+            buttons: input.buttons,
+        }
+    }
+
+    fn state(&self) -> InputState {
+        let pad = match (self.pad_x, self.pad_y) {
+            (Some(x), Some(y)) => Some(Pad { x, y }),
+            _ => None,
+        };
+        InputState {
+            pad,
+            buttons: self.buttons,
+        }
+    }
+}
+
+fn replays_dir(id: &FullID) -> PathBuf {
+    crate::dir()
+        .join("replays")
+        .join(id.author())
+        .join(id.app())
+}
+
+/// Records input into a new replay. Discarded with its copy of the data
+/// when dropped without being finished.
+pub struct InputRecorder {
+    dir: PathBuf,
+    inputs: Vec<Input>,
+}
+
+impl InputRecorder {
+    /// Start a new replay with a copy of the current app data.
+    ///
+    /// Must be called while the runtime is stopped.
+    pub fn start(id: &FullID) -> io::Result<Self> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let dir = replays_dir(id).join(now.to_string());
+        copy_dir(&data_dir(id), &dir.join("data"))?;
+        Ok(InputRecorder {
+            dir,
+            inputs: vec![],
+        })
+    }
+
+    /// Call before every runtime update with the input given to it.
+    pub fn record(&mut self, input: &InputState) {
+        let input = Input::new(input);
+        match self.inputs.last_mut() {
+            Some(last) if Input { repeat: 1, ..*last } == input => last.repeat += 1,
+            _ => self.inputs.push(input),
+        }
+    }
+
+    pub fn finish(mut self, id: &FullID) -> io::Result<PathBuf> {
+        let file = ReplayFile {
+            author: id.author().to_owned(),
+            app: id.app().to_owned(),
+            inputs: std::mem::take(&mut self.inputs),
+        };
+        let path = self.dir.join("replay.json");
+        std::fs::write(&path, miniserde::json::to_string(&file))?;
+        Ok(path)
+    }
+}
+
+impl Drop for InputRecorder {
+    fn drop(&mut self) {
+        if !self.dir.join("replay.json").exists() {
+            _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+pub struct Replay {
+    inputs: Vec<Input>,
+    /// Index of the current input
+    position: usize,
+    /// How often the current input was already given
+    repeated: u32,
+}
+
+impl Replay {
+    /// Load the most recent replay of the app and a sandbox with the data it started with.
+    pub fn load_latest(id: &FullID) -> io::Result<(Self, Sandbox)> {
+        let dir = std::fs::read_dir(replays_dir(id))?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join("replay.json").exists())
+            .max_by_key(|entry| entry.file_name().to_string_lossy().parse::<u128>().ok())
+            .ok_or(io::ErrorKind::NotFound)?
+            .path();
+        Self::load(&dir.join("replay.json"), id)
+    }
+
+    /// Load a replay file of the app and a sandbox with the data stored next to it, if any.
+    pub fn load(path: &Path, id: &FullID) -> io::Result<(Self, Sandbox)> {
+        let raw = std::fs::read_to_string(path)?;
+        let file: ReplayFile = miniserde::json::from_str(&raw)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid replay file"))?;
+        if (file.author.as_str(), file.app.as_str()) != (id.author(), id.app()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the replay is of {}.{}", file.author, file.app),
+            ));
+        }
+        let saved = path.with_file_name("data");
+        let sandbox = Sandbox::new(id, saved.exists().then_some(saved.as_path()))?;
+        Ok((Replay::new(file.inputs), sandbox))
+    }
+
+    /// Parse scripted input, one line per input:
+    ///
+    /// ```text
+    /// # updates, pressed buttons (or "-" for none) and the optional pad position
+    /// 60 -
+    /// 2 S
+    /// 30 EN 0,1000
+    /// ```
+    pub fn from_script(script: &str) -> Result<Self, String> {
+        let mut inputs = vec![];
+        for (n, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = || format!("line {}: expected `<updates> <buttons> [<x>,<y>]`", n + 1);
+            let mut parts = line.split_whitespace();
+            let repeat = parts.next().and_then(|p| p.parse().ok()).ok_or_else(err)?;
+            let mut buttons = 0;
+            for c in parts.next().ok_or_else(err)?.chars() {
+                buttons |= match c {
+                    'S' => 1 << 0,
+                    'E' => 1 << 1,
+                    'W' => 1 << 2,
+                    'N' => 1 << 3,
+                    'M' => 1 << 4,
+                    '-' => 0,
+                    _ => return Err(err()),
+                };
+            }
+            let (pad_x, pad_y) = match parts.next() {
+                Some(pad) => {
+                    let (x, y) = pad.split_once(',').ok_or_else(err)?;
+                    let x = x.parse().map_err(|_| err())?;
+                    let y = y.parse().map_err(|_| err())?;
+                    (Some(x), Some(y))
+                }
+                None => (None, None),
+            };
+            inputs.push(Input {
+                repeat,
+                pad_x,
+                pad_y,
+                buttons,
+            });
+        }
+        Ok(Replay::new(inputs))
+    }
+
+    fn new(inputs: Vec<Input>) -> Self {
+        Replay {
+            inputs,
+            position: 0,
+            repeated: 0,
+        }
+    }
+
+    /// The input for the next update, or `None` once the replay is over.
+    pub fn next(&mut self) -> Option<InputState> {
+        let input = self.inputs.get(self.position)?;
+        self.repeated += 1;
+        if self.repeated >= input.repeat {
+            self.position += 1;
+            self.repeated = 0;
+        }
+        Some(input.state())
+    }
+}
+
+/// A root directory of its own with a copy of the app and its data, for running
+/// the app without changing the real data. Removed when dropped.
+pub struct Sandbox {
+    root: PathBuf,
+}
+
+impl Sandbox {
+    /// Copy the app and the given data, or the current app data if there is none.
+    pub fn new(id: &FullID, data: Option<&Path>) -> io::Result<Self> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let sandbox = Sandbox {
+            root: dir()
+                .join("tmp")
+                .join(format!("sandbox-{}-{now}", std::process::id())),
+        };
+        let rom = Path::new("roms").join(id.author()).join(id.app());
+        copy_dir(&dir().join(&rom), &sandbox.root.join(&rom))?;
+        copy_dir(&dir().join("sys"), &sandbox.root.join("sys"))?;
+        let data = data.map_or_else(|| data_dir(id), Path::to_path_buf);
+        copy_dir(&data, &sandbox.data_dir(id))?;
+        Ok(sandbox)
+    }
+
+    /// The directory to use as the root of the device.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The data directory of the app inside the sandbox.
+    pub fn data_dir(&self, id: &FullID) -> PathBuf {
+        self.root.join("data").join(id.author()).join(id.app())
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        _ = std::fs::remove_dir_all(&self.root);
+    }
+}
@@ -0,0 +1,138 @@
+use firefly_runtime::FullID;
+use kaolin::prelude::*;
+use macroquad::prelude::*;
+
+use crate::{
+    i18n::Text,
+    nav::{self, Nav, Screen},
+    runtime_log, safe_area,
+    settings::Settings,
+    ui::{self, BUTTON_FONT_SIZE, DESCR_FONT_SIZE, TITLE_FONT_SIZE},
+};
+
+/// How many of the last log messages to show.
+const LOG_LINES: usize = 20;
+
+/// Shown when the runtime of a running app returns an error.
+pub struct ErrorScreen {
+    /// `None` if the launcher failed
+    id: Option<FullID>,
+    category: String,
+    message: String,
+    log: Vec<String>,
+    settings: Settings,
+    ui: ui::Renderer,
+}
+
+impl ErrorScreen {
+    pub fn new(id: Option<FullID>, error: firefly_runtime::Error) -> Self {
+        let settings = Settings::load();
+        let message = error.to_string();
+        runtime_log::record(format!("error: {message}"));
+        // The name of the error variant, without any of its fields.
+        let category = format!("{error:?}")
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default()
+            .to_owned();
+        let width = safe_area::rect(&settings).w;
+        let log = runtime_log::tail(LOG_LINES)
+            .iter()
+            .flat_map(|line| ui::wrap(line, DESCR_FONT_SIZE / 1.5, width))
+            .collect();
+        ErrorScreen {
+            id,
+            category,
+            message,
+            log,
+            ui: ui::Renderer::new(safe_area::insets(&settings)),
+            settings,
+        }
+    }
+
+    pub async fn show(&mut self) -> Nav {
+        let ErrorScreen {
+            id,
+            category,
+            message,
+            log,
+            settings,
+            ui,
+        } = self;
+        let theme = settings.theme;
+        let app = match id {
+            Some(id) => format!("{}.{}", id.author(), id.app()),
+            None => settings.tr(Text::Launcher).to_owned(),
+        };
+        let message = ui::wrap(message, DESCR_FONT_SIZE, safe_area::rect(settings).w);
+        loop {
+            if nav::back_pressed() {
+                return Nav::Pop;
+            }
+            ui.insets = safe_area::insets(settings);
+            clear_background(theme.background());
+            ui.draw(|k| {
+                let style = TextStyle::new()
+                    .font_size(DESCR_FONT_SIZE)
+                    .color(theme.foreground().into());
+                k.styled(
+                    FlexStyle::new()
+                        .background_color(theme.background().into())
+                        .layout(Layout::new().direction(Direction::TopToBottom).gap(10.))
+                        .sizing(sizing!(grow!())),
+                    |mut k| {
+                        k = k.text(
+                            settings.tr(Text::RuntimeError),
+                            style.font_size(TITLE_FONT_SIZE).color(RED.into()),
+                        );
+                        k = k.text(&app, style);
+                        k = k.text(category.as_str(), style.color(RED.into()));
+                        for line in &message {
+                            k = k.text(line, style);
+                        }
+                        k = k.styled(
+                            FlexStyle::new()
+                                .layout(Layout::new().direction(Direction::LeftToRight).gap(10.))
+                                .sizing(sizing!(grow!(), fit!())),
+                            |mut k| {
+                                for (button, text) in [("retry", Text::Retry), ("back", Text::Back)]
+                                {
+                                    k = k.styled(
+                                        FlexStyle::new()
+                                            .custom(button)
+                                            .background_color(theme.header().into())
+                                            .border(Border {
+                                                width: 3.,
+                                                color: theme.border().into(),
+                                            })
+                                            .sizing(sizing!(grow!(), fit!())),
+                                        |k| {
+                                            k.text(
+                                                settings.tr(text),
+                                                style.font_size(BUTTON_FONT_SIZE),
+                                            )
+                                        },
+                                    );
+                                }
+                                k
+                            },
+                        );
+                        k = k.text(settings.tr(Text::Log), style);
+                        for line in log.iter() {
+                            k = k.text(line, style.font_size(DESCR_FONT_SIZE / 1.5));
+                        }
+                        k
+                    },
+                )
+            });
+
+            match ui.clicked.iter().next().map(|s| s.as_str()) {
+                Some("retry") => return Nav::Replace(Screen::Play(id.clone())),
+                Some("back") => return Nav::Pop,
+                Some(other) => panic!("{other}"),
+                None => {}
+            }
+            next_frame().await;
+        }
+    }
+}
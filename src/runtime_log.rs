@@ -0,0 +1,66 @@
+//! The most recent messages about the running app, shown when it fails.
+
+use std::{collections::VecDeque, sync::Mutex};
+
+const CAPACITY: usize = 100;
+
+static LOG: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+pub fn record(msg: impl Into<String>) {
+    let mut log = LOG.lock().unwrap();
+    if log.len() == CAPACITY {
+        log.pop_front();
+    }
+    log.push_back(msg.into());
+}
+
+/// Also record everything the runtime, the device and the running app print.
+///
+/// They log by printing to stdout and stderr, so both are redirected into a pipe
+/// and every line is recorded before being passed on to the original stderr.
+#[cfg(unix)]
+pub fn capture_output() {
+    use std::{
+        fs::File,
+        io::{BufRead, BufReader, Write},
+        os::fd::FromRawFd,
+    };
+
+    let mut fds = [0; 2];
+    // SAFETY: only file descriptors created here are passed on, and each
+    // of them is owned by exactly one `File`.
+    let (reader, mut original) = unsafe {
+        if libc::pipe(fds.as_mut_ptr()) != 0 {
+            return;
+        }
+        let original = libc::dup(libc::STDERR_FILENO);
+        if original < 0
+            || libc::dup2(fds[1], libc::STDOUT_FILENO) < 0
+            || libc::dup2(fds[1], libc::STDERR_FILENO) < 0
+        {
+            return;
+        }
+        libc::close(fds[1]);
+        (File::from_raw_fd(fds[0]), File::from_raw_fd(original))
+    };
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            _ = writeln!(original, "{line}");
+            record(line);
+        }
+    });
+}
+
+/// Forget everything recorded so far, for example when starting a new app.
+pub fn clear() {
+    LOG.lock().unwrap().clear();
+}
+
+/// The last `n` messages, oldest first.
+pub fn tail(n: usize) -> Vec<String> {
+    let log = LOG.lock().unwrap();
+    log.iter()
+        .skip(log.len().saturating_sub(n))
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,91 @@
+use std::sync::Mutex;
+
+use macroquad::prelude::*;
+use miniserde::{Deserialize, Serialize};
+
+use crate::settings::Settings;
+
+/// Space at each edge of the screen covered by notches, status or gesture bars.
+#[derive(Serialize, Deserialize, Default, Copy, Clone, PartialEq)]
+pub struct Insets {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+/// The insets set in the settings or, if there are none, reported by the platform.
+pub fn insets(settings: &Settings) -> Insets {
+    settings.safe_area.unwrap_or_else(platform_insets)
+}
+
+/// The part of the screen that is not covered by anything.
+pub fn rect(settings: &Settings) -> Rect {
+    let Insets {
+        top,
+        right,
+        bottom,
+        left,
+    } = insets(settings);
+    Rect::new(
+        left,
+        top,
+        screen_width() - left - right,
+        screen_height() - top - bottom,
+    )
+}
+
+/// Insets reported by the platform, cached until the screen size changes.
+pub fn platform_insets() -> Insets {
+    static CACHE: Mutex<Option<(Vec2, Insets)>> = Mutex::new(None);
+    let size = vec2(screen_width(), screen_height());
+    let mut cache = CACHE.lock().unwrap();
+    match *cache {
+        Some((cached_size, insets)) if cached_size == size => insets,
+        _ => {
+            let insets = query_insets().unwrap_or_default();
+            *cache = Some((size, insets));
+            insets
+        }
+    }
+}
+
+#[cfg(target_os = "android")]
+fn query_insets() -> Option<Insets> {
+    use macroquad::miniquad::{
+        call_int_method, call_object_method,
+        native::android::{attach_jni_env, ACTIVITY},
+    };
+
+    unsafe {
+        let env = attach_jni_env();
+        let window = call_object_method!(env, ACTIVITY, "getWindow", "()Landroid/view/Window;");
+        if window.is_null() {
+            return None;
+        }
+        let view = call_object_method!(env, window, "getDecorView", "()Landroid/view/View;");
+        if view.is_null() {
+            return None;
+        }
+        let insets = call_object_method!(
+            env,
+            view,
+            "getRootWindowInsets",
+            "()Landroid/view/WindowInsets;"
+        );
+        if insets.is_null() {
+            return None;
+        }
+        Some(Insets {
+            top: call_int_method!(env, insets, "getSystemWindowInsetTop", "()I") as f32,
+            right: call_int_method!(env, insets, "getSystemWindowInsetRight", "()I") as f32,
+            bottom: call_int_method!(env, insets, "getSystemWindowInsetBottom", "()I") as f32,
+            left: call_int_method!(env, insets, "getSystemWindowInsetLeft", "()I") as f32,
+        })
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+fn query_insets() -> Option<Insets> {
+    None
+}
@@ -0,0 +1,65 @@
+//! Saving the screen of the running app as PNG.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use firefly_runtime::FullID;
+use macroquad::prelude::*;
+
+/// Write the screen at native resolution and, if `scale` is above 1,
+/// an upscaled copy next to it. Returns the path of the native one.
+pub fn save(id: &FullID, screen: &Image, scale: u8) -> io::Result<PathBuf> {
+    let dir = crate::dir()
+        .join("screenshots")
+        .join(id.author())
+        .join(id.app());
+    std::fs::create_dir_all(&dir)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = dir.join(format!("{now}.png"));
+    write_png(screen, &path)?;
+    if scale > 1 {
+        let path = dir.join(format!("{now}@{scale}x.png"));
+        write_png(&upscale(screen, scale), &path)?;
+    }
+    Ok(path)
+}
+
+/// Write the image as PNG.
+///
+/// Unlike `Image::export_png`, which is meant for pixels read back from the GPU,
+/// this doesn't flip the image upside down.
+pub fn write_png(image: &Image, path: &Path) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, image.width.into(), image.height.into());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer
+        .write_image_data(&image.bytes)
+        .map_err(io::Error::other)
+}
+
+/// Nearest neighbor scaling, so that the pixels stay sharp.
+fn upscale(image: &Image, scale: u8) -> Image {
+    let scale = usize::from(scale);
+    let width = usize::from(image.width) * scale;
+    let height = usize::from(image.height) * scale;
+    let mut bytes = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let i = ((y / scale) * usize::from(image.width) + x / scale) * 4;
+            bytes.extend_from_slice(&image.bytes[i..i + 4]);
+        }
+    }
+    Image {
+        width: width as u16,
+        height: height as u16,
+        bytes,
+    }
+}
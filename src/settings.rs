@@ -0,0 +1,661 @@
+use std::path::PathBuf;
+
+use firefly_runtime::FullID;
+use kaolin::prelude::*;
+use macroquad::{miniquad::window::show_keyboard, prelude::*};
+use miniserde::{
+    json::{self, Value},
+    Deserialize, Serialize,
+};
+
+use crate::{
+    backup::{self, BackupScreen},
+    dir,
+    i18n::Text,
+    input::key_name,
+    nav::{self, Nav, Screen},
+    runtime_log,
+    safe_area::{self, Insets},
+    ui::{self, BUTTON_FONT_SIZE, DESCR_FONT_SIZE, TITLE_FONT_SIZE},
+};
+
+pub const DEFAULT_CATALOG_URL: &str = "https://catalog.fireflyzero.com/";
+const AUDIO_LATENCIES: [u16; 4] = [20, 40, 80, 160];
+const SCREENSHOT_SCALES: [u8; 4] = [1, 2, 4, 8];
+/// How much tapping a safe area edge increases the inset, in pixels.
+const INSET_STEP: f32 = 16.;
+const MAX_INSET: f32 = 160.;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Settings {
+    /// Base URL of the catalog, with a trailing slash
+    pub catalog_url: String,
+    pub theme: Theme,
+    pub keys: KeyBindings,
+    pub touch_layout: TouchLayout,
+    /// Master volume in percent
+    pub volume: u8,
+    /// How far audio may lag behind the picture, in milliseconds
+    pub audio_latency: u16,
+    pub scaling: Scaling,
+    pub language: Language,
+    /// Manually set safe area, overriding the one reported by the platform
+    pub safe_area: Option<Insets>,
+    /// Also save screenshots enlarged by this factor, if above 1
+    pub screenshot_scale: u8,
+    /// Where backups are written to and imported from, instead of the shared default
+    pub backup_dir: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            catalog_url: DEFAULT_CATALOG_URL.to_owned(),
+            theme: Theme::Light,
+            keys: KeyBindings::default(),
+            touch_layout: TouchLayout::PadLeft,
+            volume: 100,
+            audio_latency: 80,
+            scaling: Scaling::Fit,
+            language: Language::System,
+            safe_area: None,
+            screenshot_scale: 1,
+            backup_dir: None,
+        }
+    }
+}
+
+fn path() -> PathBuf {
+    dir().join("settings.json")
+}
+
+impl Settings {
+    /// Read the settings file, falling back to defaults if it is missing or invalid.
+    ///
+    /// Fields the file doesn't have yet, because it was written by an older version,
+    /// get their default value instead of discarding the whole file.
+    pub fn load() -> Self {
+        let Some(saved) = std::fs::read_to_string(path())
+            .ok()
+            .and_then(|raw| json::from_str::<Value>(&raw).ok())
+        else {
+            return Settings::default();
+        };
+        let mut settings: Value = json::from_str(&json::to_string(&Settings::default())).unwrap();
+        merge(&mut settings, saved);
+        json::from_str(&json::to_string(&settings)).unwrap_or_default()
+    }
+
+    /// Write the settings to the file, logging the error if that fails.
+    ///
+    /// The settings stay in effect until the app is closed either way.
+    pub fn save(&self) {
+        let result = std::fs::create_dir_all(dir())
+            .and_then(|()| std::fs::write(path(), json::to_string(self)));
+        if let Err(e) = result {
+            runtime_log::record(format!("failed to save settings: {e}"));
+        }
+    }
+}
+
+/// Replace the values in `defaults` with the ones from `saved`, keeping the fields it lacks.
+fn merge(defaults: &mut Value, saved: Value) {
+    match (defaults, saved) {
+        (Value::Object(defaults), Value::Object(saved)) => {
+            for (key, value) in saved {
+                match defaults.get_mut(&key) {
+                    Some(default) => merge(default, value),
+                    None => {
+                        defaults.insert(key, value);
+                    }
+                }
+            }
+        }
+        (default, value) => *default = value,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+impl Theme {
+    fn next(self) -> Self {
+        match self {
+            Theme::Light => Theme::Dark,
+            Theme::Dark => Theme::Light,
+        }
+    }
+
+    pub fn background(self) -> Color {
+        match self {
+            Theme::Light => GRAY,
+            Theme::Dark => Color::from_rgba(30, 30, 30, 255),
+        }
+    }
+
+    pub fn header(self) -> Color {
+        match self {
+            Theme::Light => WHITE,
+            Theme::Dark => DARKGRAY,
+        }
+    }
+
+    pub fn foreground(self) -> Color {
+        match self {
+            Theme::Light => BLACK,
+            Theme::Dark => WHITE,
+        }
+    }
+
+    pub fn border(self) -> Color {
+        match self {
+            Theme::Light => DARKGRAY,
+            Theme::Dark => BLACK,
+        }
+    }
+}
+
+/// On which side of the screen the touch pad is placed.
+/// The buttons go on the other side.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum TouchLayout {
+    PadLeft,
+    PadRight,
+}
+
+impl TouchLayout {
+    fn next(self) -> Self {
+        match self {
+            TouchLayout::PadLeft => TouchLayout::PadRight,
+            TouchLayout::PadRight => TouchLayout::PadLeft,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Scaling {
+    /// Use as much space as possible
+    Fit,
+    /// Only scale by whole numbers so that all pixels have the same size
+    Integer,
+}
+
+impl Scaling {
+    fn next(self) -> Self {
+        match self {
+            Scaling::Fit => Scaling::Integer,
+            Scaling::Integer => Scaling::Fit,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Language {
+    /// Use the language of the operating system
+    System,
+    English,
+    German,
+}
+
+impl Language {
+    fn next(self) -> Self {
+        match self {
+            Language::System => Language::English,
+            Language::English => Language::German,
+            Language::German => Language::System,
+        }
+    }
+}
+
+/// Names of the keyboard keys bound to each Firefly input.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct KeyBindings {
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+    pub up: Vec<String>,
+    pub down: Vec<String>,
+    /// Touch the pad without moving in any direction
+    pub center: Vec<String>,
+    pub s: Vec<String>,
+    pub e: Vec<String>,
+    pub w: Vec<String>,
+    pub n: Vec<String>,
+    pub menu: Vec<String>,
+    /// Open the emulator's pause menu
+    pub pause: Vec<String>,
+    /// Switch to the next speed
+    pub speed: Vec<String>,
+    /// Stop the clock and only update when the step key is pressed
+    pub frame_advance: Vec<String>,
+    /// Run a single update in frame advance mode
+    pub step: Vec<String>,
+    /// Keep the current input for the following steps
+    pub hold_input: Vec<String>,
+    /// Save the screen as PNG
+    pub screenshot: Vec<String>,
+    /// Start or stop recording a clip
+    pub record: Vec<String>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        fn keys(keys: &[KeyCode]) -> Vec<String> {
+            keys.iter().copied().map(key_name).collect()
+        }
+        KeyBindings {
+            left: keys(&[KeyCode::Left, KeyCode::Key4]),
+            right: keys(&[KeyCode::Right, KeyCode::Key6]),
+            up: keys(&[KeyCode::Up, KeyCode::Key8]),
+            down: keys(&[KeyCode::Down, KeyCode::Key2]),
+            center: keys(&[KeyCode::Key5]),
+            s: keys(&[KeyCode::Z, KeyCode::Enter, KeyCode::Space]),
+            e: keys(&[KeyCode::X, KeyCode::B, KeyCode::Backspace]),
+            w: keys(&[KeyCode::A]),
+            n: keys(&[KeyCode::Y, KeyCode::S]),
+            menu: keys(&[KeyCode::Back, KeyCode::Tab]),
+            pause: keys(&[KeyCode::Escape, KeyCode::P]),
+            speed: keys(&[KeyCode::F]),
+            frame_advance: keys(&[KeyCode::F9]),
+            step: keys(&[KeyCode::F10]),
+            hold_input: keys(&[KeyCode::F8]),
+            screenshot: keys(&[KeyCode::F12]),
+            record: keys(&[KeyCode::F11]),
+        }
+    }
+}
+
+impl KeyBindings {
+    fn actions_mut(&mut self) -> [(&'static str, Text, &mut Vec<String>); 17] {
+        [
+            ("left", Text::ActionLeft, &mut self.left),
+            ("right", Text::ActionRight, &mut self.right),
+            ("up", Text::ActionUp, &mut self.up),
+            ("down", Text::ActionDown, &mut self.down),
+            ("center", Text::ActionCenter, &mut self.center),
+            ("S", Text::ActionS, &mut self.s),
+            ("E", Text::ActionE, &mut self.e),
+            ("W", Text::ActionW, &mut self.w),
+            ("N", Text::ActionN, &mut self.n),
+            ("menu", Text::ActionMenu, &mut self.menu),
+            ("pause", Text::ActionPause, &mut self.pause),
+            ("speed", Text::ActionSpeed, &mut self.speed),
+            (
+                "frame advance",
+                Text::ActionFrameAdvance,
+                &mut self.frame_advance,
+            ),
+            ("step", Text::ActionStep, &mut self.step),
+            ("hold input", Text::ActionHoldInput, &mut self.hold_input),
+            ("screenshot", Text::ActionScreenshot, &mut self.screenshot),
+            ("record", Text::ActionRecord, &mut self.record),
+        ]
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Edit {
+    CatalogUrl,
+    BackupDir,
+    /// Waiting for a key press to bind to the given action
+    Key(&'static str),
+}
+
+struct Row {
+    id: String,
+    label: String,
+    value: String,
+}
+
+fn rows(settings: &mut Settings, editing: Option<Edit>) -> Vec<Row> {
+    let row = |id: &str, label: &str, value: String| Row {
+        id: id.to_owned(),
+        label: label.to_owned(),
+        value,
+    };
+    let language = match settings.language {
+        Language::System => settings.tr(Text::SystemLanguage),
+        Language::English => "English",
+        Language::German => "Deutsch",
+    };
+    let mut rows = vec![
+        row(
+            "catalog_url",
+            settings.tr(Text::CatalogUrl),
+            if editing == Some(Edit::CatalogUrl) {
+                format!("{}_", settings.catalog_url)
+            } else {
+                settings.catalog_url.clone()
+            },
+        ),
+        row(
+            "theme",
+            settings.tr(Text::Theme),
+            settings.tr(settings.theme.text()).to_owned(),
+        ),
+        row(
+            "volume",
+            settings.tr(Text::Volume),
+            format!("{}%", settings.volume),
+        ),
+        row(
+            "audio_latency",
+            settings.tr(Text::AudioLatency),
+            format!("{} ms", settings.audio_latency),
+        ),
+        row(
+            "screenshot_scale",
+            settings.tr(Text::ScreenshotScale),
+            match settings.screenshot_scale {
+                1 => settings.tr(Text::Native).to_owned(),
+                scale => format!("{scale}x"),
+            },
+        ),
+        row(
+            "scaling",
+            settings.tr(Text::Scaling),
+            settings.tr(settings.scaling.text()).to_owned(),
+        ),
+        row(
+            "touch_layout",
+            settings.tr(Text::TouchLayout),
+            settings.tr(settings.touch_layout.text()).to_owned(),
+        ),
+        row("language", settings.tr(Text::Language), language.to_owned()),
+        row(
+            "safe_area",
+            settings.tr(Text::SafeArea),
+            settings
+                .tr(if settings.safe_area.is_some() {
+                    Text::Manual
+                } else {
+                    Text::Auto
+                })
+                .to_owned(),
+        ),
+    ];
+    if let Some(insets) = settings.safe_area {
+        for (id, edge, value) in [
+            ("inset_top", Text::Top, insets.top),
+            ("inset_right", Text::Right, insets.right),
+            ("inset_bottom", Text::Bottom, insets.bottom),
+            ("inset_left", Text::Left, insets.left),
+        ] {
+            let label = format!("{} {}", settings.tr(Text::SafeArea), settings.tr(edge));
+            rows.push(row(id, &label, format!("{value}")));
+        }
+    }
+    let key = settings.tr(Text::Key);
+    let press_a_key = settings.tr(Text::PressAKey);
+    // Joined up front, translating needs the settings borrowed again.
+    let actions = settings
+        .keys
+        .actions_mut()
+        .map(|(action, text, keys)| (action, text, keys.join(", ")));
+    for (action, text, keys) in actions {
+        let value = if editing == Some(Edit::Key(action)) {
+            press_a_key.to_owned()
+        } else {
+            keys
+        };
+        rows.push(row(
+            &format!("key:{action}"),
+            &format!("{key} {}", settings.tr(text)),
+            value,
+        ));
+    }
+    rows.push(row(
+        "reset_keys",
+        settings.tr(Text::ResetKeys),
+        String::new(),
+    ));
+    rows.push(row(
+        "backup_dir",
+        settings.tr(Text::BackupDir),
+        match &settings.backup_dir {
+            Some(dir) if editing == Some(Edit::BackupDir) => format!("{dir}_"),
+            _ => backup::backups_dir(settings).display().to_string(),
+        },
+    ));
+    rows.push(row("backup", settings.tr(Text::Backup), String::new()));
+    rows
+}
+
+pub struct SettingsScreen {
+    settings: Settings,
+    editing: Option<Edit>,
+    /// The app paused underneath, if opened from the pause menu
+    running: Option<FullID>,
+    ui: ui::Renderer,
+}
+
+impl SettingsScreen {
+    pub fn new() -> Self {
+        let settings = Settings::load();
+        SettingsScreen {
+            editing: None,
+            running: None,
+            ui: ui::Renderer::new(safe_area::insets(&settings)),
+            settings,
+        }
+    }
+
+    /// The settings opened from the pause menu of the running app.
+    pub fn paused(running: &FullID) -> Self {
+        SettingsScreen {
+            running: Some(running.clone()),
+            ..SettingsScreen::new()
+        }
+    }
+
+    pub async fn show(&mut self) -> Nav {
+        let SettingsScreen {
+            settings,
+            editing,
+            running,
+            ui,
+        } = self;
+        loop {
+            match *editing {
+                Some(Edit::CatalogUrl) => {
+                    while let Some(c) = get_char_pressed() {
+                        if !c.is_control() {
+                            settings.catalog_url.push(c);
+                        }
+                    }
+                    if is_key_pressed(KeyCode::Backspace) {
+                        settings.catalog_url.pop();
+                    }
+                    if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::Back) {
+                        if !settings.catalog_url.ends_with('/') {
+                            settings.catalog_url.push('/');
+                        }
+                        settings.save();
+                        show_keyboard(false);
+                        *editing = None;
+                    }
+                }
+                Some(Edit::BackupDir) => {
+                    let dir = settings.backup_dir.get_or_insert_default();
+                    while let Some(c) = get_char_pressed() {
+                        if !c.is_control() {
+                            dir.push(c);
+                        }
+                    }
+                    if is_key_pressed(KeyCode::Backspace) {
+                        dir.pop();
+                    }
+                    if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::Back) {
+                        // Clearing the path goes back to the default.
+                        if dir.is_empty() {
+                            settings.backup_dir = None;
+                        }
+                        settings.save();
+                        show_keyboard(false);
+                        *editing = None;
+                    }
+                }
+                Some(Edit::Key(action)) => {
+                    if let Some(key) = get_last_key_pressed() {
+                        if key != KeyCode::Escape {
+                            for (name, _, keys) in settings.keys.actions_mut() {
+                                if name == action {
+                                    *keys = vec![key_name(key)];
+                                }
+                            }
+                            settings.save();
+                        }
+                        *editing = None;
+                    }
+                }
+                None => {
+                    if nav::back_pressed() {
+                        return Nav::Pop;
+                    }
+                }
+            }
+
+            let theme = settings.theme;
+            ui.insets = safe_area::insets(settings);
+            clear_background(theme.background());
+            let title = settings.tr(Text::Settings);
+            let rows = rows(settings, *editing);
+            let label_width = rows
+                .iter()
+                .map(|row| measure_text(&row.label, None, DESCR_FONT_SIZE as _, 1.).width as u32)
+                .max()
+                .unwrap()
+                .min(safe_area::rect(settings).w as u32 / 2) as f64;
+            ui.draw(|k| {
+                let style = TextStyle::new()
+                    .font_size(DESCR_FONT_SIZE)
+                    .color(theme.foreground().into());
+                let border = || Border {
+                    width: 3.,
+                    color: theme.border().into(),
+                };
+                k.styled(
+                    FlexStyle::new()
+                        .background_color(theme.background().into())
+                        .layout(Layout::new().direction(Direction::TopToBottom))
+                        .sizing(sizing!(grow!())),
+                    |mut k| {
+                        k = k.styled(
+                            FlexStyle::new()
+                                .background_color(theme.header().into())
+                                .layout(
+                                    Layout::new()
+                                        .alignment(Alignment::Center)
+                                        .justification(Justification::Center),
+                                )
+                                .sizing(sizing!(grow!(), fit!())),
+                            |k| k.text(title, style.font_size(TITLE_FONT_SIZE)),
+                        );
+                        for row in &rows {
+                            k = k.styled(
+                                FlexStyle::new()
+                                    .custom(row.id.as_str())
+                                    .border(border())
+                                    .layout(Layout::new().direction(Direction::LeftToRight))
+                                    .sizing(sizing!(grow!(), fit!())),
+                                |k| {
+                                    k.styled(
+                                        FlexStyle::new()
+                                            .border(border())
+                                            .sizing(sizing!(fixed!(label_width), grow!())),
+                                        |k| k.text(&row.label, style),
+                                    )
+                                    .styled(
+                                        FlexStyle::new().border(border()).sizing(sizing!(grow!())),
+                                        |k| {
+                                            k.text(
+                                                &row.value,
+                                                style.font_size(BUTTON_FONT_SIZE / 2.),
+                                            )
+                                        },
+                                    )
+                                },
+                            );
+                        }
+                        k
+                    },
+                )
+            });
+
+            if let Some(id) = ui.clicked.iter().next() {
+                match id.as_str() {
+                    "catalog_url" => {
+                        show_keyboard(true);
+                        *editing = Some(Edit::CatalogUrl);
+                    }
+                    "theme" => settings.theme = settings.theme.next(),
+                    "volume" => settings.volume = (settings.volume + 10) % 110,
+                    "audio_latency" => {
+                        let i = AUDIO_LATENCIES
+                            .iter()
+                            .position(|l| *l == settings.audio_latency)
+                            .map_or(0, |i| i + 1);
+                        settings.audio_latency = AUDIO_LATENCIES[i % AUDIO_LATENCIES.len()];
+                    }
+                    "screenshot_scale" => {
+                        let i = SCREENSHOT_SCALES
+                            .iter()
+                            .position(|s| *s == settings.screenshot_scale)
+                            .map_or(0, |i| i + 1);
+                        settings.screenshot_scale = SCREENSHOT_SCALES[i % SCREENSHOT_SCALES.len()];
+                    }
+                    "scaling" => settings.scaling = settings.scaling.next(),
+                    "touch_layout" => settings.touch_layout = settings.touch_layout.next(),
+                    "language" => settings.language = settings.language.next(),
+                    "backup_dir" => {
+                        let dir = backup::backups_dir(settings).display().to_string();
+                        settings.backup_dir.get_or_insert(dir);
+                        show_keyboard(true);
+                        *editing = Some(Edit::BackupDir);
+                    }
+                    "reset_keys" => settings.keys = KeyBindings::default(),
+                    "backup" => {
+                        let screen = BackupScreen::new(running.as_ref());
+                        return Nav::Push(Screen::Backup(screen));
+                    }
+                    "safe_area" => {
+                        settings.safe_area = match settings.safe_area {
+                            Some(_) => None,
+                            None => Some(safe_area::platform_insets()),
+                        };
+                    }
+                    edge @ ("inset_top" | "inset_right" | "inset_bottom" | "inset_left") => {
+                        let insets = settings.safe_area.get_or_insert_default();
+                        let value = match edge {
+                            "inset_top" => &mut insets.top,
+                            "inset_right" => &mut insets.right,
+                            "inset_bottom" => &mut insets.bottom,
+                            _ => &mut insets.left,
+                        };
+                        *value = (*value / INSET_STEP).floor() * INSET_STEP + INSET_STEP;
+                        if *value > MAX_INSET {
+                            *value = 0.;
+                        }
+                    }
+                    other => {
+                        let action = other
+                            .strip_prefix("key:")
+                            .unwrap_or_else(|| panic!("{other}"));
+                        let (action, _, _) = settings
+                            .keys
+                            .actions_mut()
+                            .into_iter()
+                            .find(|(name, _, _)| *name == action)
+                            .unwrap();
+                        *editing = Some(Edit::Key(action));
+                    }
+                }
+                settings.save();
+            }
+
+            next_frame().await;
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Play statistics of apps, updated around every session like the device does.
+
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use firefly_runtime::FullID;
+use firefly_types::{Encode as _, Stats};
+
+use crate::runtime_log;
+
+fn path(id: &FullID) -> PathBuf {
+    crate::dir()
+        .join("data")
+        .join(id.author())
+        .join(id.app())
+        .join("stats")
+}
+
+pub fn load(id: &FullID) -> Option<Stats> {
+    let raw = std::fs::read(path(id)).ok()?;
+    Stats::decode(&raw).ok()
+}
+
+fn save(id: &FullID, stats: &Stats) {
+    match stats.encode_vec() {
+        Ok(raw) => {
+            if let Err(e) = std::fs::write(path(id), raw) {
+                runtime_log::record(format!("failed to save stats: {e}"));
+            }
+        }
+        Err(e) => runtime_log::record(format!("failed to encode stats: {e}")),
+    }
+}
+
+/// The current date in UTC as (year, month, day).
+pub fn today() -> (u16, u8, u8) {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    // Days since 1970-01-01 to a civil date, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year as u16, month as u8, day as u8)
+}
+
+/// A running app. Counts the launch when created and the play time when dropped.
+///
+/// Restarts of the app continue the same session.
+pub struct Session {
+    id: FullID,
+    /// Play time before the last pause
+    played: Duration,
+    /// When the app was last resumed, `None` while paused
+    resumed: Option<Instant>,
+    /// Index into the stats arrays, which are bucketed by the number of players
+    players: usize,
+}
+
+impl Session {
+    /// Start a session of the app played by the given number of players, this device included.
+    pub fn start(id: &FullID, players: usize) -> Self {
+        let players = players.clamp(1, 4) - 1;
+        if let Some(mut stats) = load(id) {
+            stats.launches[players] = stats.launches[players].saturating_add(1);
+            stats.launched_on = today();
+            save(id, &stats);
+        }
+        Session {
+            id: id.clone(),
+            played: Duration::ZERO,
+            resumed: Some(Instant::now()),
+            players,
+        }
+    }
+
+    pub fn id(&self) -> &FullID {
+        &self.id
+    }
+
+    /// Stop or continue counting the play time, for example while the pause menu is open.
+    pub fn set_playing(&mut self, playing: bool) {
+        match (playing, self.resumed) {
+            (true, None) => self.resumed = Some(Instant::now()),
+            (false, Some(resumed)) => {
+                self.played += resumed.elapsed();
+                self.resumed = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.set_playing(false);
+        let Some(mut stats) = load(&self.id) else {
+            return;
+        };
+        // Rounded, so that a session of a few seconds short of a minute still counts.
+        let minutes = ((self.played.as_secs() + 30) / 60) as _;
+        let i = self.players;
+        stats.minutes[i] = stats.minutes[i].saturating_add(minutes);
+        stats.longest_play[i] = stats.longest_play[i].max(minutes);
+        save(&self.id, &stats);
+    }
+}
@@ -0,0 +1,108 @@
+use macroquad::prelude::*;
+
+/// The Firefly Zero device updates apps 60 times per second.
+pub const UPDATES_PER_SECOND: f64 = 60.;
+const UPDATE_TIME: f64 = 1. / UPDATES_PER_SECOND;
+/// If rendering falls behind further than this, the missed updates are dropped
+/// instead of running them all at once and falling even further behind.
+const MAX_UPDATES_PER_FRAME: u32 = 4;
+
+/// How fast the app runs compared to the real device.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Speed {
+    Quarter,
+    Half,
+    Normal,
+    Double,
+    Quadruple,
+    /// As many updates as fit into a frame
+    Unlimited,
+}
+
+impl Speed {
+    pub fn next(self) -> Self {
+        match self {
+            Speed::Quarter => Speed::Half,
+            Speed::Half => Speed::Normal,
+            Speed::Normal => Speed::Double,
+            Speed::Double => Speed::Quadruple,
+            Speed::Quadruple => Speed::Unlimited,
+            Speed::Unlimited => Speed::Quarter,
+        }
+    }
+
+    fn factor(self) -> Option<f64> {
+        match self {
+            Speed::Quarter => Some(0.25),
+            Speed::Half => Some(0.5),
+            Speed::Normal => Some(1.),
+            Speed::Double => Some(2.),
+            Speed::Quadruple => Some(4.),
+            Speed::Unlimited => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Speed::Quarter => "0.25x",
+            Speed::Half => "0.5x",
+            Speed::Normal => "1x",
+            Speed::Double => "2x",
+            Speed::Quadruple => "4x",
+            Speed::Unlimited => "max",
+        }
+    }
+}
+
+/// Decides how many runtime updates to run per rendered frame,
+/// so that apps run at the same speed regardless of the display refresh rate.
+pub struct FixedStep {
+    /// Time of the previous call to [`FixedStep::updates`]
+    last: f64,
+    /// Time that passed but wasn't covered by an update yet
+    accumulated: f64,
+    pub speed: Speed,
+    /// Updates per frame at [`Speed::Unlimited`], adjusted to the frame rate
+    unlimited: u32,
+}
+
+impl FixedStep {
+    pub fn new() -> Self {
+        FixedStep {
+            last: get_time(),
+            accumulated: 0.,
+            speed: Speed::Normal,
+            unlimited: MAX_UPDATES_PER_FRAME,
+        }
+    }
+
+    /// Forget about the time that passed since the last update,
+    /// for example after the app was paused.
+    pub fn reset(&mut self) {
+        self.last = get_time();
+        self.accumulated = 0.;
+    }
+
+    /// How many updates need to run to catch up with the wall clock.
+    pub fn updates(&mut self) -> u32 {
+        let now = get_time();
+        let elapsed = now - self.last;
+        self.last = now;
+        let Some(factor) = self.speed.factor() else {
+            // Run more updates per frame as long as the frame rate stays up.
+            if elapsed < UPDATE_TIME * 1.5 {
+                self.unlimited += 1;
+            } else {
+                self.unlimited = (self.unlimited - 1).max(1);
+            }
+            return self.unlimited;
+        };
+        self.accumulated += elapsed * factor;
+        let updates = (self.accumulated / UPDATE_TIME) as u32;
+        // Subtract whole updates instead of resetting to zero
+        // so that the leftover time carries over and nothing drifts.
+        self.accumulated -= f64::from(updates) * UPDATE_TIME;
+        let max = (f64::from(MAX_UPDATES_PER_FRAME) * factor.max(1.)) as u32;
+        updates.min(max)
+    }
+}
@@ -6,10 +6,84 @@ use std::{
 use kaolin::prelude::*;
 use macroquad::{miniquad::date::now, prelude::*};
 
-use crate::ui::gestures::{gesture, Gesture};
+use crate::{
+    safe_area::Insets,
+    ui::gestures::{gesture, Gesture},
+};
 
 mod gestures;
 
+pub const TITLE_FONT_SIZE: f32 = if cfg!(target_os = "android") {
+    150.
+} else {
+    80.
+};
+pub const BUTTON_FONT_SIZE: f32 = if cfg!(target_os = "android") {
+    120.
+} else {
+    50.
+};
+pub const DESCR_FONT_SIZE: f32 = if cfg!(target_os = "android") {
+    80.
+} else {
+    30.
+};
+
+/// Split the text into lines that fit into the given width.
+///
+/// Kaolin does not wrap text on its own, so long texts need to be split up front.
+pub fn wrap(text: &str, font_size: f32, max_width: f32) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for c in paragraph.chars() {
+            line.push(c);
+            if measure_text(&line, None, font_size as u16, 1.).width > max_width {
+                let c = line.pop().unwrap();
+                lines.push(std::mem::take(&mut line));
+                line.push(c);
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// How long a toast stays on the screen, in seconds.
+const TOAST_TIME: f64 = 2.;
+
+/// A short message shown at the bottom of the screen for a moment.
+pub struct Toast {
+    text: String,
+    until: f64,
+}
+
+impl Toast {
+    pub fn new(text: String) -> Self {
+        Toast {
+            text,
+            until: get_time() + TOAST_TIME,
+        }
+    }
+
+    /// Draw the message, returning false once it has expired.
+    pub fn draw(&self) -> bool {
+        let size = measure_text(&self.text, None, DESCR_FONT_SIZE as u16, 1.);
+        let x = screen_width() / 2. - size.width / 2.;
+        let y = screen_height() - size.height * 3.;
+        let margin = size.height / 2.;
+        draw_rectangle(
+            x - margin,
+            y - size.offset_y - margin,
+            size.width + margin * 2.,
+            size.height + margin * 2.,
+            macroquad::prelude::Color::new(0., 0., 0., 0.8),
+        );
+        draw_text(&self.text, x, y, DESCR_FONT_SIZE, WHITE);
+        get_time() < self.until
+    }
+}
+
 #[derive(Default, PartialEq, Copy, Clone)]
 pub struct Color(pub macroquad::prelude::Color);
 
@@ -37,16 +111,19 @@ pub struct Renderer {
     scrolling: f32,
     /// How far to offset the entire screen on the y axis
     scroll: f32,
+    /// Screen edges to keep the UI away from
+    pub insets: Insets,
 }
 
 impl Renderer {
-    pub fn new() -> Self {
+    pub fn new(insets: Insets) -> Self {
         Renderer {
             touches: HashMap::new(),
             tapped: vec![],
             clicked: HashSet::new(),
             scrolling: 0.,
             scroll: 0.,
+            insets,
         }
     }
 }
@@ -130,8 +207,17 @@ impl<'frame> KaolinRenderer<'frame, Color, &'frame str> for Renderer {
 
         self.clicked.clear();
 
+        let Insets {
+            top,
+            right,
+            bottom,
+            left,
+        } = self.insets;
         let kaolin = Kaolin::new(
-            (screen_width() as i32, screen_height() as i32),
+            (
+                (screen_width() - left - right) as i32,
+                (screen_height() - top - bottom) as i32,
+            ),
             move |text, config| {
                 let TextDimensions { width, .. } =
                     measure_text(text, None, config.font_size as u16, 1.0);
@@ -152,28 +238,19 @@ impl<'frame> KaolinRenderer<'frame, Color, &'frame str> for Renderer {
                     border,
                     custom,
                 } => {
-                    draw_rectangle(
-                        x as f32,
-                        y as f32 + self.scroll,
-                        width as f32,
-                        height as f32,
-                        color.0,
-                    );
+                    let x = x as f32 + left;
+                    let y = y as f32 + top + self.scroll;
+                    draw_rectangle(x, y, width as f32, height as f32, color.0);
                     draw_rectangle_lines(
-                        x as f32,
-                        y as f32 + self.scroll,
+                        x,
+                        y,
                         width as f32,
                         height as f32,
                         border.width,
                         border.color.0,
                     );
                     if !custom.is_empty() {
-                        let rect = Rect::new(
-                            x as f32,
-                            y as f32 + self.scroll,
-                            width as f32,
-                            height as f32,
-                        );
+                        let rect = Rect::new(x, y, width as f32, height as f32);
                         if self.tapped.iter().any(|t| rect.contains(*t))
                             || (is_mouse_button_pressed(MouseButton::Left)
                                 && rect.contains(mouse_position().into()))
@@ -193,8 +270,8 @@ impl<'frame> KaolinRenderer<'frame, Color, &'frame str> for Renderer {
                     let dims = measure_text("Bg", None, font_size as u16, 1.0);
                     draw_text(
                         text.as_str(),
-                        x as f32,
-                        y as f32 + dims.height + self.scroll,
+                        x as f32 + left,
+                        y as f32 + top + dims.height + self.scroll,
                         font_size,
                         color.0,
                     );
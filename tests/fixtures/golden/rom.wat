@@ -0,0 +1,27 @@
+;; Source of `_bin`, the tiny ROM used by the golden image tests.
+;; Fills the screen with blue, or with red while any button is pressed.
+;; On top, a white bar near the top moves right by a pixel every update
+;; and a yellow bar stands still near the bottom right, so that flipped,
+;; shifted or reordered rows and stale frames all show up as differences.
+(module
+  (import "graphics" "clear_screen" (func $clear_screen (param i32)))
+  (import "input" "read_buttons" (func $read_buttons (param i32) (result i32)))
+  (import "graphics" "draw_rect"
+    (func $draw_rect (param i32 i32 i32 i32 i32 i32 i32)))
+  (global $color (mut i32) (i32.const 10))
+  (global $frame (mut i32) (i32.const 0))
+  (func (export "update")
+    (global.set $frame (i32.add (global.get $frame) (i32.const 1)))
+    (global.set $color
+      (select
+        (i32.const 3)
+        (i32.const 10)
+        ;; combined input of all peers
+        (call $read_buttons (i32.const 255)))))
+  (func (export "render")
+    (call $clear_screen (global.get $color))
+    ;; x, y, width, height, fill color, stroke color, stroke width
+    (call $draw_rect (global.get $frame) (i32.const 20) (i32.const 30) (i32.const 10)
+      (i32.const 13) (i32.const 0) (i32.const 0))
+    (call $draw_rect (i32.const 200) (i32.const 100) (i32.const 5) (i32.const 40)
+      (i32.const 5) (i32.const 0) (i32.const 0))))
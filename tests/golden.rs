@@ -0,0 +1,117 @@
+//! Golden image tests: run the fixture ROM headless with scripted input
+//! and compare the screen at chosen frames against stored PNGs.
+//!
+//! Run with `BLESS=1` to replace the goldens with the current output.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use firefly_types::Encode as _;
+
+const AUTHOR: &str = "test";
+const APP: &str = "golden";
+const FRAMES: [u32; 3] = [5, 15, 25];
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden")
+}
+
+/// Install the fixture ROM into a fresh data root and return the root.
+fn install() -> PathBuf {
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden-root");
+    if root.exists() {
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+    let rom = root.join("roms").join(AUTHOR).join(APP);
+    std::fs::create_dir_all(&rom).unwrap();
+    std::fs::copy(fixtures().join("_bin"), rom.join("_bin")).unwrap();
+    let meta = firefly_types::Meta {
+        app_id: APP,
+        app_name: "Golden",
+        author_id: AUTHOR,
+        author_name: "Test",
+        launcher: false,
+        sudo: false,
+        version: 1,
+    };
+    std::fs::write(rom.join("_meta"), meta.encode_vec().unwrap()).unwrap();
+    root
+}
+
+fn read_png(path: &Path) -> (u32, u32, Vec<u8>) {
+    let file = std::fs::File::open(path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    buf.truncate(info.buffer_size());
+    assert_eq!(info.color_type, png::ColorType::Rgba, "{}", path.display());
+    (info.width, info.height, buf)
+}
+
+/// Describe how the actual image differs from the expected one, if at all.
+fn diff(expected: &Path, actual: &Path) -> Option<String> {
+    let (ew, eh, expected) = read_png(expected);
+    let (aw, ah, actual) = read_png(actual);
+    if (ew, eh) != (aw, ah) {
+        return Some(format!("size is {aw}x{ah}, expected {ew}x{eh}"));
+    }
+    let mut report = vec![];
+    let mut differing = 0;
+    for (i, (e, a)) in expected.chunks(4).zip(actual.chunks(4)).enumerate() {
+        if e == a {
+            continue;
+        }
+        differing += 1;
+        // Only list the first few pixels, the count tells the rest.
+        if report.len() < 5 {
+            let (x, y) = (i as u32 % ew, i as u32 / ew);
+            report.push(format!("  ({x}, {y}): {a:02x?}, expected {e:02x?}"));
+        }
+    }
+    if differing == 0 {
+        return None;
+    }
+    let total = ew * eh;
+    report.insert(0, format!("{differing} of {total} pixels differ:"));
+    Some(report.join("\n"))
+}
+
+#[test]
+fn golden_frames() {
+    let root = install();
+    let out = root.join("out");
+    let dump = FRAMES.map(|f| f.to_string()).join(",");
+    let status = Command::new(env!("CARGO_BIN_EXE_fireflydroid"))
+        .arg("--root")
+        .arg(&root)
+        .arg("--headless")
+        .arg(format!("{AUTHOR}.{APP}"))
+        .args(["--frames", &FRAMES[FRAMES.len() - 1].to_string()])
+        .arg("--input")
+        .arg(fixtures().join("input.txt"))
+        .arg("--out")
+        .arg(&out)
+        .args(["--dump", &dump])
+        .status()
+        .unwrap();
+    assert!(status.success(), "headless run failed: {status}");
+
+    let bless = std::env::var_os("BLESS").is_some();
+    let mut failures = vec![];
+    for frame in FRAMES {
+        let golden = fixtures().join(format!("frame-{frame}.png"));
+        let actual = out.join(format!("screen-{frame}.png"));
+        if bless {
+            std::fs::copy(&actual, &golden).unwrap();
+            continue;
+        }
+        if let Some(report) = diff(&golden, &actual) {
+            failures.push(format!("frame {frame} ({}): {report}", actual.display()));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
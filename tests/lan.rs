@@ -0,0 +1,57 @@
+//! LAN discovery: two instances on the same machine find each other over loopback.
+
+use std::process::{Command, Output, Stdio};
+
+/// How long each instance looks for the other, in seconds.
+const SECONDS: &str = "5";
+
+fn spawn(name: &str) -> std::process::Child {
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    Command::new(env!("CARGO_BIN_EXE_fireflydroid"))
+        .arg("--root")
+        .arg(root)
+        .args(["peers", SECONDS])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
+/// The own port, the addresses of the instances found, and the peers for the device.
+fn parse(output: &Output) -> (String, Vec<String>, Vec<String>) {
+    assert!(output.status.success(), "peers failed: {}", output.status);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut port = None;
+    let mut found = vec![];
+    let mut peers = vec![];
+    for line in stdout.lines() {
+        if let Some(p) = line.strip_prefix("listening on port ") {
+            port = Some(p.to_owned());
+        } else if let Some(addr) = line.strip_prefix("found ") {
+            found.push(addr.to_owned());
+        } else if let Some(ip) = line.strip_prefix("peer ") {
+            peers.push(ip.to_owned());
+        }
+    }
+    (port.expect(&stdout), found, peers)
+}
+
+#[test]
+fn two_instances_find_each_other() {
+    let a = spawn("lan-a");
+    let b = spawn("lan-b");
+    let (port_a, found_a, peers_a) = parse(&a.wait_with_output().unwrap());
+    let (port_b, found_b, peers_b) = parse(&b.wait_with_output().unwrap());
+    assert_ne!(port_a, port_b);
+
+    let a_from_b = format!("127.0.0.1:{port_a}");
+    let b_from_a = format!("127.0.0.1:{port_b}");
+    assert!(found_a.contains(&b_from_a), "a found {found_a:?}");
+    assert!(found_b.contains(&a_from_b), "b found {found_b:?}");
+    // Their own announcements coming back don't count.
+    assert!(!found_a.contains(&a_from_b), "a found itself: {found_a:?}");
+    assert!(!found_b.contains(&b_from_a), "b found itself: {found_b:?}");
+    // The devices reach each other on the same address of this machine, told apart
+    // by their ports, and listen on it themselves.
+    assert_eq!(peers_a.len(), 1, "a has peers {peers_a:?}");
+    assert_eq!(peers_a, peers_b);
+}